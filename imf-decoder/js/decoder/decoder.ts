@@ -1,33 +1,39 @@
 import * as tf from '@tensorflow/tfjs';
-import { PlayerStatus, MessageType, ReferenceData } from '../types';
+import { PlayerStatus, MessageType, WorkerMessage, ErrorDetails, FramePayload, TokenPacket } from '../types';
+import { request, tokenBuffers } from './protocol';
 
 export class IMFPlayer {
     private worker: Worker;
     private status: PlayerStatus = PlayerStatus.Idle;
     private model: tf.GraphModel | null = null;
     private callbacks: Map<string, Function> = new Map();
+    private frameIndex: number = 0;
 
-    constructor(width: number, height: number) {
+    constructor(private width: number, private height: number) {
         this.worker = new Worker(new URL('./decoder.worker', import.meta.url));
         this.setupWorkerListeners();
     }
 
     private setupWorkerListeners() {
-        this.worker.onmessage = (event) => {
-            const { type, data, error } = event.data;
-            
+        this.worker.onmessage = (event: MessageEvent<WorkerMessage>) => {
+            const { type, data } = event.data.message;
+
             const callback = this.callbacks.get(type);
             if (callback) {
                 callback(data);
                 this.callbacks.delete(type);
             }
 
-            switch (type as MessageType) {
-                case MessageType.DecoderCreated:
+            switch (type) {
+                case MessageType.DecoderReady:
                     this.status = PlayerStatus.Ready;
                     break;
-                case MessageType.Error:
-                    console.error('Decoder error:', error);
+                case MessageType.DecoderOpenError:
+                    this.status = PlayerStatus.Error;
+                    console.error('Decoder error:', (data as ErrorDetails).message);
+                    break;
+                case MessageType.DecoderError:
+                    console.error('Decoder error:', (data as ErrorDetails).message);
                     break;
             }
         };
@@ -35,51 +41,38 @@ export class IMFPlayer {
 
     async initialize(): Promise<void> {
         return new Promise((resolve, reject) => {
-            this.callbacks.set(MessageType.DecoderInited, resolve);
-            this.worker.postMessage({ type: MessageType.DecoderInit });
+            this.callbacks.set(MessageType.DecoderReady, resolve);
+            this.callbacks.set(MessageType.DecoderOpenError, (error: ErrorDetails) => reject(new Error(error.message)));
+            this.worker.postMessage(request(MessageType.DecoderInit, { width: this.width, height: this.height }));
+        });
+    }
+
+    // Tokens are only decoded once the worker has started
+    async start(): Promise<void> {
+        if (this.status !== PlayerStatus.Ready && this.status !== PlayerStatus.Pause) {
+            throw new Error('Decoder not ready');
+        }
+        return new Promise((resolve) => {
+            this.callbacks.set(MessageType.DecoderStarted, () => {
+                this.status = PlayerStatus.Playing;
+                resolve();
+            });
+            this.worker.postMessage(request(MessageType.DecoderStart));
         });
     }
 
     async loadModel(modelUrl: string): Promise<boolean> {
         try {
             this.model = await tf.loadGraphModel(modelUrl);
-            const referenceData = this.createReferenceData();
-            
-            return new Promise((resolve, reject) => {
-                this.callbacks.set(MessageType.ReferenceDataSet, (status: boolean) => {
-                    resolve(status);
-                });
-
-                this.worker.postMessage({
-                    type: MessageType.DecoderInit,
-                    data: referenceData
-                });
-            });
+            return true;
         } catch (error) {
             console.error('Failed to load model:', error);
             return false;
         }
     }
 
-    private createReferenceData(): ReferenceData {
-        const features = [
-            tf.zeros([1, 128, 64, 64]),
-            tf.zeros([1, 256, 32, 32]),
-            tf.zeros([1, 512, 16, 16]),
-            tf.zeros([1, 512, 8, 8])
-        ];
-        const token = tf.zeros([1, 32]);
-
-        return {
-            features: features.map(tensor => ({
-                tensor: tensor.dataSync() as Float32Array,
-                shape: tensor.shape
-            })),
-            token: token.dataSync() as Float32Array
-        };
-    }
-
-    async processFrame(inputData: Float32Array, inputShape: number[]): Promise<void> {
+    // Runs the model and decodes its output as one token; resolves with the frame
+    async processFrame(inputData: Float32Array, inputShape: number[]): Promise<FramePayload | void> {
         if (!this.model) return;
 
         try {
@@ -87,16 +80,15 @@ export class IMFPlayer {
             const outputTensor = await this.model.executeAsync(inputTensor);
             const outputData = await (outputTensor as tf.Tensor).data();
 
-            return new Promise((resolve) => {
-                this.callbacks.set(MessageType.TokensProcessed, resolve);
-                
-                this.worker.postMessage({
-                    type: MessageType.TokensProcessed,
-                    data: [{
-                        token: Array.from(outputData),
-                        frame_index: 0
-                    }]
-                });
+            return new Promise((resolve, reject) => {
+                this.callbacks.set(MessageType.DecodedVideoFrame, resolve);
+                this.callbacks.set(MessageType.DecoderError, (error: ErrorDetails) => reject(new Error(error.message)));
+
+                const tokens: TokenPacket[] = [{
+                    token: Float32Array.from(outputData),
+                    frame_index: this.frameIndex++
+                }];
+                this.worker.postMessage(request(MessageType.DecodeVideoBuffer, tokens), tokenBuffers(tokens));
             });
         } catch (error) {
             console.error('Error processing frame:', error);
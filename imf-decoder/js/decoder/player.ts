@@ -4,11 +4,14 @@ import {
     MessageType, 
     DecoderConfig,
     WorkerMessage, 
-    ReferenceData,
+    ErrorDetails,
+    FramePayload,
+    TokenPacket,
     FrameStats,
     ModelInputConfig,
     FrameData
 } from '../types';
+import { request, tokenBuffers } from './protocol';
 
 export class IMFPlayer {
     private worker: Worker;
@@ -24,6 +27,7 @@ export class IMFPlayer {
     private frameCallback: ((stats: FrameStats) => void) | null = null;
     private errorCallback: ((error: Error) => void) | null = null;
     private lastFrameTime: number = 0;
+    private framesDecoded: number = 0;
 
     constructor(config: DecoderConfig) {
         this.config = config;
@@ -43,7 +47,7 @@ export class IMFPlayer {
 
     private setupWorkerListeners() {
         this.worker.onmessage = (event: MessageEvent<WorkerMessage>) => {
-            const { type, data, error } = event.data;
+            const { type, data } = event.data.message;
             
             const callback = this.callbacks.get(type);
             if (callback) {
@@ -51,20 +55,22 @@ export class IMFPlayer {
                 this.callbacks.delete(type);
             }
 
-            if (error) {
-                this.handleDecoderError(error);
-                return;
-            }
-
             switch (type) {
-                case MessageType.DecoderCreated:
+                case MessageType.DecoderReady:
                     this.status = PlayerStatus.Ready;
                     break;
-                case MessageType.FrameProcessed:
-                    this.handleFrameProcessed(data);
+                case MessageType.DecodedVideoFrame:
+                    this.handleFrameProcessed(data as FramePayload);
+                    break;
+                case MessageType.DecoderOpenError:
+                    this.status = PlayerStatus.Error;
+                    this.handleDecoderError((data as ErrorDetails).message);
                     break;
-                case MessageType.DecoderRecovered:
-                    this.handleDecoderRecovery(data);
+                case MessageType.DecoderError:
+                    this.handleDecoderError((data as ErrorDetails).message);
+                    break;
+                case MessageType.DecoderResetDone:
+                    this.handleDecoderRecovery();
                     break;
             }
         };
@@ -76,54 +82,25 @@ export class IMFPlayer {
 
     async initialize(): Promise<void> {
         return new Promise((resolve, reject) => {
-            this.callbacks.set(MessageType.DecoderInited, resolve);
-            this.worker.postMessage({ 
-                type: MessageType.DecoderInit,
-                data: this.config
-            });
+            this.callbacks.set(MessageType.DecoderReady, resolve);
+            this.callbacks.set(MessageType.DecoderOpenError, (error: ErrorDetails) => reject(new Error(error.message)));
+            this.worker.postMessage(request(MessageType.DecoderInit, {
+                width: this.config.width,
+                height: this.config.height
+            }));
         });
     }
 
     async loadModel(modelUrl: string): Promise<boolean> {
         try {
             this.model = await tf.loadGraphModel(modelUrl);
-            const referenceData = this.createReferenceData();
-            
-            return new Promise((resolve) => {
-                this.callbacks.set(MessageType.ReferenceDataSet, (status: boolean) => {
-                    resolve(status);
-                });
-
-                this.worker.postMessage({
-                    type: MessageType.DecoderInit,
-                    data: referenceData
-                });
-            });
+            return true;
         } catch (error) {
             console.error('Failed to load model:', error);
             return false;
         }
     }
 
-    private createReferenceData(): ReferenceData {
-        const features = [
-            tf.zeros([1, 128, 64, 64]),
-            tf.zeros([1, 256, 32, 32]),
-            tf.zeros([1, 512, 16, 16]),
-            tf.zeros([1, 512, 8, 8])
-        ];
-
-        const token = tf.zeros([1, 32]);
-
-        return {
-            features: features.map(tensor => ({
-                tensor: tensor.dataSync() as Float32Array,
-                shape: tensor.shape
-            })),
-            token: token.dataSync() as Float32Array
-        };
-    }
-
     private async getNextFrameData(): Promise<FrameData> {
         try {
             if (this.lastProcessedFrame) {
@@ -163,21 +140,26 @@ export class IMFPlayer {
 
         try {
             const frameData = await this.getNextFrameData();
-            this.worker.postMessage({
-                type: MessageType.ProcessFrame,
-                data: [{
-                    token: Array.from(frameData.data.values),
-                    frame_index: frameData.frameIndex
-                }]
-            });
+            const tokens: TokenPacket[] = [{
+                token: Float32Array.from(frameData.data.values as ArrayLike<number>),
+                frame_index: frameData.frameIndex
+            }];
+            this.worker.postMessage(request(MessageType.DecodeVideoBuffer, tokens), tokenBuffers(tokens));
         } catch (error) {
             this.handleDecoderError(error);
         }
     }
 
-    private handleFrameProcessed(data: any) {
+    private handleFrameProcessed(frame: FramePayload) {
+        this.framesDecoded += 1;
         if (this.frameCallback) {
-            this.frameCallback(data.frameStats);
+            this.frameCallback({
+                frameTime: performance.now() - this.lastFrameTime,
+                gpuTime: 0,
+                frameCount: this.framesDecoded,
+                droppedFrames: 0,
+                lastFrameTimestamp: frame.timestamp
+            });
         }
 
         if (this.status === PlayerStatus.Playing) {
@@ -192,11 +174,10 @@ export class IMFPlayer {
         }
     }
 
-    private handleDecoderRecovery(data: any) {
-        console.log('Decoder recovered:', data);
-        if (data.success && this.status === PlayerStatus.Playing) {
-            this.requestNextFrame();
-        }
+    // A reset leaves the decoder idle; it has to be initialized again
+    private handleDecoderRecovery() {
+        console.log('Decoder reset');
+        this.status = PlayerStatus.Idle;
     }
 
     private requestNextFrame() {
@@ -234,16 +215,28 @@ export class IMFPlayer {
     }
 
     public async start() {
-        if (this.status !== PlayerStatus.Ready) {
+        if (this.status !== PlayerStatus.Ready && this.status !== PlayerStatus.Pause) {
             throw new Error('Decoder not ready');
         }
         await this.preloadFrames();
+        // Tokens are only decoded once the worker has started
+        await new Promise((resolve) => {
+            this.callbacks.set(MessageType.DecoderStarted, resolve);
+            this.worker.postMessage(request(MessageType.DecoderStart));
+        });
         this.status = PlayerStatus.Playing;
         this.requestNextFrame();
     }
 
     public stop() {
-        this.status = PlayerStatus.Ready;
+        if (this.status !== PlayerStatus.Playing) return;
+        this.status = PlayerStatus.Pause;
+        this.worker.postMessage(request(MessageType.DecoderPause));
+    }
+
+    // Sends DecoderReset, e.g. after a failed open
+    public reset() {
+        this.worker.postMessage(request(MessageType.DecoderReset));
     }
 
     public async destroy() {
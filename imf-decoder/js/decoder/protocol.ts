@@ -0,0 +1,15 @@
+// js/decoder/protocol.ts
+import { MessageType, PROTOCOL_VERSION, TokenPacket, WorkerMessage } from '../types';
+
+let nextRequestId = 0;
+
+// Wraps a message in the versioned envelope DecoderWorker expects
+export function request(type: MessageType, data?: WorkerMessage['message']['data']): WorkerMessage {
+    nextRequestId += 1;
+    return { version: PROTOCOL_VERSION, requestId: nextRequestId, message: { type, data } };
+}
+
+// Token buffers to transfer with a DecodeVideoBuffer instead of copying them
+export function tokenBuffers(tokens: TokenPacket[]): ArrayBuffer[] {
+    return tokens.flatMap(packet => packet.token instanceof Float32Array ? [packet.token.buffer as ArrayBuffer] : []);
+}
@@ -1,57 +1,24 @@
 // js/decoder/worker.ts
-import init, { IMFDecoder } from '../../pkg/imf_decoder';
-import { MessageType, DecoderStatus } from '../types';
-
-class DecoderWorkerInstance {
-    private decoder: IMFDecoder | null = null;
-    private status: DecoderStatus = DecoderStatus.Idle;
-
-    async initialize() {
-        try {
-            await init();
-            this.decoder = new IMFDecoder(1920, 1080);
-            this.status = DecoderStatus.Ready;
-            self.postMessage({ type: MessageType.DecoderCreated });
-        } catch (error) {
-            self.postMessage({ 
-                type: MessageType.Error, 
-                error: error instanceof Error ? error.message : String(error)
-            });
-        }
-    }
-
-    handleMessage(event: MessageEvent) {
-        const { type, data } = event.data;
-
-        try {
-            switch (type as MessageType) {
-                case MessageType.DecoderInit:
-                    this.initialize();
-                    break;
-                case MessageType.TokensProcessed:
-                    this.processTokens(data);
-                    break;
-            }
-        } catch (error) {
-            self.postMessage({ 
-                type: MessageType.Error, 
-                error: error instanceof Error ? error.message : String(error)
-            });
-        }
+// The decoder runs in Rust; this only loads the module and forwards
+// messages. Replies are posted by DecoderWorker itself.
+import init, { DecoderWorker } from '@pkg/imf_decoder';
+
+// The Player posts DecoderInit as soon as the worker exists, so anything
+// that arrives while the module loads is held and replayed in order
+let worker: DecoderWorker | null = null;
+const pending: unknown[] = [];
+
+self.onmessage = (event: MessageEvent) => {
+    if (worker) {
+        worker.handle_message(event.data);
+    } else {
+        pending.push(event.data);
     }
+};
 
-    async processTokens(tokens: any) {
-        if (!this.decoder || this.status !== DecoderStatus.Ready) {
-            throw new Error('Decoder not ready');
-        }
-
-        const result = await this.decoder.process_tokens(tokens);
-        self.postMessage({ type: MessageType.TokensProcessed, result });
+await init();
 
-        const batchResult = await this.decoder.process_batch();
-        self.postMessage({ type: MessageType.BatchProcessed, result: batchResult });
-    }
+worker = new DecoderWorker();
+for (const data of pending.splice(0)) {
+    worker.handle_message(data);
 }
-
-const worker = new DecoderWorkerInstance();
-self.onmessage = (event) => worker.handleMessage(event);
\ No newline at end of file
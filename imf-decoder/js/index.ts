@@ -1,4 +1,5 @@
 import { init, verifyWasmBuild, runDecoderTests } from './utils/wasm-test';
+import type { IMFDecoder } from '@pkg/imf_decoder';
 import { 
     WasmModule,  
     ReferenceData, 
@@ -316,14 +317,18 @@ class TestUI {
         // Remove all existing status classes
         element.className = 'status-value';
         
-        // Add appropriate status class
-        switch(status) {
-            case 0: element.classList.add('status-idle'); break;
-            case 1: element.classList.add('status-ready'); break;
-            case 2: element.classList.add('status-playing'); break;
-            case 3: element.classList.add('status-paused'); break;
-            default: element.classList.add('status-error');
-        }
+        // Add appropriate status class; player and decoder number their
+        // statuses differently, so go by name
+        const statusClasses: Record<string, string> = {
+            Idle: 'status-idle',
+            Initializing: 'status-ready',
+            Inited: 'status-ready',
+            Ready: 'status-ready',
+            Playing: 'status-playing',
+            Open: 'status-playing',
+            Pause: 'status-paused'
+        };
+        element.classList.add(statusClasses[statusName] ?? 'status-error');
         
         element.textContent = statusName;
     }
@@ -468,7 +473,7 @@ class TestUI {
             }
 
             await this.decoder.start_player_loop();
-            this.updateStatus('decoder', DecoderStatus.Playing);
+            this.updateStatus('decoder', DecoderStatus.Open);
             this.log('success', 'Decoder loop started');
 
             // Start frame processing
@@ -69,6 +69,20 @@ declare module '@pkg/imf_decoder' {
         get_playback_direction(): number;
     }
 
+    // Runs the decoder inside a worker; replies go out through postMessage
+    export class DecoderWorker {
+        constructor();
+        free(): void;
+        handle_message(data: unknown): void;
+        initialize(width: number, height: number): void;
+        start(): void;
+        pause(): void;
+        close(): void;
+        reset(): void;
+        process_frame(tokens: FrameToken[]): void;
+        get_status(): unknown;
+    }
+
     export function initSync(): void;
     export function start(): void;
     export default function init(): Promise<void>;
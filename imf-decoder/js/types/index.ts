@@ -8,20 +8,23 @@ export enum PlayerStatus {
   Ready = 1,
   Playing = 2,
   Pause = 3,
-  Destroyed = 4
+  Destroyed = 4,
+  Closed = 5,
+  Error = 6
 }
 
+// Mirrors DecoderStatus in src/decoder/protocol.rs
 export enum DecoderStatus {
   Idle = 0,
   Initializing = 1,
-  Ready = 2,
-  Open = 3,
-  Pause = 4,
-  Closed = 5
+  Inited = 2,
+  Ready = 3,
+  Open = 4,
+  Pause = 5,
+  Closed = 6,
+  Error = 7
 }
 
-
-
 // Configuration Interfaces
 export interface DecoderConfig {
   width: number;
@@ -47,6 +50,13 @@ export interface FrameToken {
   frame_index: number;
 }
 
+// A token as sent in DecodeVideoBuffer; arrays are transferred, not copied
+export interface TokenPacket {
+  frame_index: number;
+  token: Float32Array | number[];
+  is_keyframe?: boolean;
+}
+
 // WASM Related Interfaces
 export interface WasmModule {
   IMFDecoder: new (width: number, height: number) => IMFDecoder;
@@ -70,36 +80,74 @@ export interface TestResult {
   error?: string;
 }
 
-// Worker Message Interface
-export interface WorkerMessage {
-  type: MessageType;
-  data?: any;
-  error?: string;
-}
-
+// Worker protocol, mirrors src/decoder/protocol.rs
+export const PROTOCOL_VERSION = 4;
 
 export enum MessageType {
+  // Main thread to worker
   DecoderInit = 'DecoderInit',
-  DecoderInited = 'DecoderInited',
-  ProcessFrame = 'ProcessFrame',
-  FrameProcessed = 'FrameProcessed',
-  UpdateRenderPass = 'UpdateRenderPass',
-  DecoderError = 'DecoderError',
-  DecoderRecovered = 'DecoderRecovered',
-  DecoderMetrics = 'DecoderMetrics',
+  DecoderStart = 'DecoderStart',
+  DecoderPause = 'DecoderPause',
+  DecoderClose = 'DecoderClose',
+  DecoderReset = 'DecoderReset',
+  DecodeVideoBuffer = 'DecodeVideoBuffer',
+  RequestStats = 'RequestStats',
+  // Worker to main thread
   DecoderCreated = 'DecoderCreated',
   WasmLoaded = 'WasmLoaded',
+  DecoderInited = 'DecoderInited',
   DecoderReady = 'DecoderReady',
-  ReferenceDataSet = 'ReferenceDataSet',
-  TokensProcessed = 'TokensProcessed',
-  BatchProcessed = 'BatchProcessed',
-  Error = 'Error'
+  DecoderOpenError = 'DecoderOpenError',
+  DecoderStarted = 'DecoderStarted',
+  DecoderPaused = 'DecoderPaused',
+  DecoderClosed = 'DecoderClosed',
+  DecoderResetDone = 'DecoderResetDone',
+  DecodedVideoFrame = 'DecodedVideoFrame',
+  DecoderStats = 'DecoderStats',
+  DecoderError = 'DecoderError'
+}
+
+export interface DecoderOptions {
+  width: number;
+  height: number;
+  settings?: {
+    targetFps?: number;
+    adaptiveFps?: boolean;
+    debugMode?: boolean;
+  };
+}
+
+export interface FramePayload {
+  frameIndex: number;
+  timestamp: number;
+  width: number;
+  height: number;
+  isKeyframe: boolean;
+  format: string;
+  data: Uint8Array;
+}
+
+export interface ErrorDetails {
+  kind: 'Protocol' | 'InvalidState' | 'Decode';
+  message: string;
+  transition?: unknown;
+}
+
+export interface StatusSnapshot {
+  status: keyof typeof DecoderStatus; // Serialized by name
+  framesDecoded: number;
+  framesDropped: number;
+  lastFrameIndex: number | null;
+  queueDepths: [number, number, number];
 }
 
 export interface WorkerMessage {
-  type: MessageType;
-  data?: any;
-  error?: string;
+  version: number;
+  requestId?: number;
+  message: {
+    type: MessageType;
+    data?: DecoderOptions | TokenPacket[] | FramePayload | ErrorDetails | StatusSnapshot;
+  };
 }
 
 export interface FrameStats {
@@ -0,0 +1,171 @@
+// Rate-distortion sweep over the token codec.
+//
+//   rd_sweep sweep --width W --height H --tokens FILE --ground-truth FILE
+//                  [--reference FILE] [--fps 25] [--bits 2,3,4,5,6,8]
+//                  [--format csv|json] [--output FILE]
+//   rd_sweep bd-rate ANCHOR.csv TEST.csv [--metric psnr_y|ssim|ms_ssim]
+//
+// Tokens are little-endian f32 values, one width*height*4 token per frame
+// back to back. Ground truth is raw 8-bit RGBA frames back to back. The
+// reference is the f32 payload sent before the tokens; only its size counts.
+
+use std::collections::HashMap;
+use std::fs;
+use std::process::ExitCode;
+
+use imf_decoder::decoder::Frame;
+use imf_decoder::quality::rd::{bd_rate, parse_csv, rd_sweep, to_csv, to_json, RdInput, RdMetric};
+
+const USAGE: &str = "usage:
+  rd_sweep sweep --width W --height H --tokens FILE --ground-truth FILE [--reference FILE] [--fps 25] [--bits 2,3,4,5,6,8] [--format csv|json] [--output FILE]
+  rd_sweep bd-rate ANCHOR.csv TEST.csv [--metric psnr_y|ssim|ms_ssim]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("sweep") => sweep(&args[1..]),
+        Some("bd-rate") => compare(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn sweep(args: &[String]) -> Result<String, String> {
+    let (options, positional) = parse_options(args)?;
+    if !positional.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let width: usize = required(&options, "width")?;
+    let height: usize = required(&options, "height")?;
+    let fps: f64 = optional(&options, "fps")?.unwrap_or(25.0);
+    let bits: Vec<u8> = match options.get("bits") {
+        Some(list) => list
+            .split(',')
+            .map(|b| b.trim().parse().map_err(|_| format!("Bad --bits value '{}'", b)))
+            .collect::<Result<_, _>>()?,
+        None => vec![2, 3, 4, 5, 6, 8],
+    };
+
+    let frame_len = width * height * 4;
+    if frame_len == 0 {
+        return Err("Width and height must be positive".to_string());
+    }
+
+    let tokens = read_f32(required_path(&options, "tokens")?)?;
+    if tokens.len() % frame_len != 0 {
+        return Err(format!("Token file holds {} values, not a multiple of {}", tokens.len(), frame_len));
+    }
+
+    let pixels = read(required_path(&options, "ground-truth")?)?;
+    if pixels.len() % frame_len != 0 {
+        return Err(format!("Ground-truth file holds {} bytes, not a multiple of {}", pixels.len(), frame_len));
+    }
+    let ground_truth = pixels
+        .chunks_exact(frame_len)
+        .map(|data| {
+            let mut frame = Frame::new(width, height);
+            frame.set_data(data.to_vec());
+            frame
+        })
+        .collect();
+
+    let reference_bits = match options.get("reference") {
+        Some(path) => read(path)?.len() * 8,
+        None => 0,
+    };
+
+    let input = RdInput {
+        width,
+        height,
+        fps,
+        reference_bits,
+        tokens: tokens.chunks_exact(frame_len).map(<[f32]>::to_vec).collect(),
+        ground_truth,
+    };
+    let points = rd_sweep(&input, &bits)?;
+
+    let output = match options.get("format").map(String::as_str) {
+        None | Some("csv") => to_csv(&points),
+        Some("json") => to_json(&points),
+        Some(other) => return Err(format!("Unknown format '{}', expected csv or json", other)),
+    };
+
+    match options.get("output") {
+        Some(path) => {
+            fs::write(path, output).map_err(|e| format!("Can't write {}: {}", path, e))?;
+            Ok(String::new())
+        }
+        None => Ok(output),
+    }
+}
+
+fn compare(args: &[String]) -> Result<String, String> {
+    let (options, positional) = parse_options(args)?;
+    let [anchor, test] = positional.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let metric: RdMetric = optional(&options, "metric")?.unwrap_or_default();
+    let anchor = parse_csv(&read_text(anchor)?)?;
+    let test = parse_csv(&read_text(test)?)?;
+    Ok(format!("BD-rate: {:.2}%\n", bd_rate(&anchor, &test, metric)?))
+}
+
+// `--name value` pairs plus everything else in order
+fn parse_options(args: &[String]) -> Result<(HashMap<String, String>, Vec<String>), String> {
+    let mut options = HashMap::new();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                let value = args.next().ok_or(format!("--{} needs a value", name))?;
+                options.insert(name.to_string(), value.clone());
+            }
+            None => positional.push(arg.clone()),
+        }
+    }
+    Ok((options, positional))
+}
+
+fn optional<T: std::str::FromStr>(options: &HashMap<String, String>, name: &str) -> Result<Option<T>, String> {
+    options
+        .get(name)
+        .map(|value| value.parse().map_err(|_| format!("Bad --{} value '{}'", name, value)))
+        .transpose()
+}
+
+fn required<T: std::str::FromStr>(options: &HashMap<String, String>, name: &str) -> Result<T, String> {
+    optional(options, name)?.ok_or(format!("--{} is required", name))
+}
+
+fn required_path<'a>(options: &'a HashMap<String, String>, name: &str) -> Result<&'a str, String> {
+    options.get(name).map(String::as_str).ok_or(format!("--{} is required", name))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))
+}
+
+fn read_f32(path: &str) -> Result<Vec<f32>, String> {
+    let bytes = read(path)?;
+    if bytes.len() % 4 != 0 {
+        return Err(format!("{} isn't a whole number of f32 values", path));
+    }
+    Ok(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RateChangeReason {
+    // Decode time alone no longer fits the frame budget
+    DecodeTooSlow,
+    // Decode + render exceeds the frame budget
+    RenderTooSlow,
+    // Load is well under budget again, step back up
+    Headroom,
+    // Target changed from outside (set_target_fps)
+    TargetChanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateChange {
+    pub fps: u32,
+    pub previous_fps: u32,
+    pub reason: RateChangeReason,
+    pub decode_time: f64,
+    pub render_time: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveConfig {
+    pub min_fps: u32,
+    pub max_fps: u32,
+    // Samples averaged before a decision, also the cooldown after a change
+    pub window: usize,
+    // Lower the rate when load exceeds this fraction of the frame budget
+    pub overload_ratio: f64,
+    // Raise the rate when load stays under this fraction of the budget
+    pub headroom_ratio: f64,
+    pub step_up: u32,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            min_fps: 1,
+            max_fps: 60,
+            window: 30,
+            overload_ratio: 0.9,
+            headroom_ratio: 0.6,
+            step_up: 5,
+        }
+    }
+}
+
+// Picks an effective frame rate from measured per-frame decode and render times
+pub struct AdaptiveRateController {
+    config: AdaptiveConfig,
+    fps: u32,
+    decode_times: VecDeque<f64>,
+    render_times: VecDeque<f64>,
+    history: VecDeque<RateChange>,
+}
+
+const MAX_HISTORY: usize = 32;
+
+impl AdaptiveRateController {
+    pub fn new(config: AdaptiveConfig) -> Self {
+        Self {
+            fps: config.max_fps,
+            config,
+            decode_times: VecDeque::with_capacity(config.window),
+            render_times: VecDeque::with_capacity(config.window),
+            history: VecDeque::with_capacity(MAX_HISTORY),
+        }
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    pub fn last_change(&self) -> Option<&RateChange> {
+        self.history.back()
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &RateChange> {
+        self.history.iter()
+    }
+
+    // New ceiling from the user; the effective rate never exceeds it
+    pub fn set_max_fps(&mut self, max_fps: u32) -> Option<RateChange> {
+        self.config.max_fps = max_fps.max(self.config.min_fps);
+        let fps = self.fps.min(self.config.max_fps);
+        if fps == self.fps {
+            return None;
+        }
+        Some(self.change(fps, RateChangeReason::TargetChanged, 0.0, 0.0))
+    }
+
+    // Feeds one frame's decode and render time (ms). Returns the change, if any.
+    pub fn observe(&mut self, decode_time: f64, render_time: f64) -> Option<RateChange> {
+        push_window(&mut self.decode_times, decode_time, self.config.window);
+        push_window(&mut self.render_times, render_time, self.config.window);
+
+        if self.decode_times.len() < self.config.window {
+            return None;
+        }
+
+        let decode = average(&self.decode_times);
+        let render = average(&self.render_times);
+        let load = decode + render;
+        let budget = 1000.0 / self.fps as f64;
+
+        if load > budget * self.config.overload_ratio && self.fps > self.config.min_fps {
+            // Drop straight to a rate the measured load can sustain
+            let sustainable = (1000.0 * self.config.overload_ratio / load).floor() as u32;
+            let fps = sustainable.clamp(self.config.min_fps, self.fps - 1);
+            let reason = if decode > budget * self.config.overload_ratio {
+                RateChangeReason::DecodeTooSlow
+            } else {
+                RateChangeReason::RenderTooSlow
+            };
+            return Some(self.change(fps, reason, decode, render));
+        }
+
+        if self.fps < self.config.max_fps {
+            let fps = (self.fps + self.config.step_up).min(self.config.max_fps);
+            let next_budget = 1000.0 / fps as f64;
+            if load < budget * self.config.headroom_ratio
+                && load < next_budget * self.config.overload_ratio
+            {
+                return Some(self.change(fps, RateChangeReason::Headroom, decode, render));
+            }
+        }
+
+        None
+    }
+
+    fn change(&mut self, fps: u32, reason: RateChangeReason, decode_time: f64, render_time: f64) -> RateChange {
+        let change = RateChange {
+            fps,
+            previous_fps: self.fps,
+            reason,
+            decode_time,
+            render_time,
+        };
+        self.fps = fps;
+
+        // Start a fresh window so the next decision sees the new rate
+        self.decode_times.clear();
+        self.render_times.clear();
+
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(change);
+        change
+    }
+}
+
+fn push_window(values: &mut VecDeque<f64>, value: f64, window: usize) {
+    values.push_back(value);
+    while values.len() > window {
+        values.pop_front();
+    }
+}
+
+fn average(values: &VecDeque<f64>) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> AdaptiveRateController {
+        AdaptiveRateController::new(AdaptiveConfig {
+            max_fps: 30,
+            window: 4,
+            ..AdaptiveConfig::default()
+        })
+    }
+
+    fn feed(controller: &mut AdaptiveRateController, decode: f64, render: f64) -> Option<RateChange> {
+        (0..4).filter_map(|_| controller.observe(decode, render)).last()
+    }
+
+    #[test]
+    fn test_lowers_rate_when_decode_is_slow() {
+        let mut controller = controller();
+
+        let change = feed(&mut controller, 60.0, 5.0).expect("rate should drop");
+        assert_eq!(change.reason, RateChangeReason::DecodeTooSlow);
+        assert_eq!(change.previous_fps, 30);
+        assert_eq!(change.fps, 13);
+        assert_eq!(controller.fps(), 13);
+    }
+
+    #[test]
+    fn test_render_time_counts_towards_budget() {
+        let mut controller = controller();
+
+        let change = feed(&mut controller, 15.0, 20.0).expect("rate should drop");
+        assert_eq!(change.reason, RateChangeReason::RenderTooSlow);
+        assert!(change.fps < 30);
+    }
+
+    #[test]
+    fn test_raises_rate_when_headroom_returns() {
+        let mut controller = controller();
+        feed(&mut controller, 60.0, 5.0);
+        assert_eq!(controller.fps(), 13);
+
+        let change = feed(&mut controller, 5.0, 2.0).expect("rate should rise");
+        assert_eq!(change.reason, RateChangeReason::Headroom);
+        assert_eq!(change.fps, 18);
+
+        // Keeps stepping but never above the ceiling
+        for _ in 0..10 {
+            feed(&mut controller, 5.0, 2.0);
+        }
+        assert_eq!(controller.fps(), 30);
+    }
+
+    #[test]
+    fn test_steady_load_keeps_rate() {
+        let mut controller = controller();
+        assert!(feed(&mut controller, 20.0, 5.0).is_none());
+        assert_eq!(controller.fps(), 30);
+        assert!(controller.last_change().is_none());
+    }
+
+    #[test]
+    fn test_lower_ceiling_clamps_rate() {
+        let mut controller = controller();
+        let change = controller.set_max_fps(24).expect("rate should follow ceiling");
+        assert_eq!(change.reason, RateChangeReason::TargetChanged);
+        assert_eq!(controller.fps(), 24);
+    }
+}
@@ -0,0 +1,193 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+use super::frame::Frame;
+use super::signals::{read_barcode, FrameStamp};
+
+// What the analyzer found so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameAnalysisReport {
+    pub frames_observed: usize,
+    pub unreadable: usize,     // No valid barcode
+    pub unique_frames: usize,
+    pub dropped: usize,        // Indices skipped over
+    pub duplicated: usize,     // Same index as the previous frame
+    pub out_of_order: usize,   // Index behind the newest one seen
+    pub first_index: Option<u32>,
+    pub last_index: Option<u32>,
+    pub mean_interval: f64,    // Between new frames, in ms
+    pub jitter: f64,           // RMS of observed minus stamped interval, in ms
+    pub max_pacing_error: f64, // Largest absolute pacing error, in ms
+}
+
+// How far behind the newest frame a late one can still fill a gap
+const LATE_WINDOW: u32 = 1024;
+
+// Reads the barcode from every rendered or decoded frame and checks the
+// sequence is complete, in order and evenly paced
+#[derive(Debug, Default)]
+pub struct FrameAnalyzer {
+    report: FrameAnalysisReport,
+    previous: Option<FrameStamp>,
+    newest: Option<u32>,
+    missing: BTreeSet<u32>, // Skipped indices within LATE_WINDOW of the newest
+    last_new: Option<(FrameStamp, f64)>,
+    interval_sum: f64,
+    intervals: usize,
+    error_squares: f64,
+    paced: usize,
+}
+
+impl FrameAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `observed_at` is when the frame was shown, in ms
+    pub fn observe(&mut self, frame: &Frame, observed_at: f64) -> Option<FrameStamp> {
+        self.report.frames_observed += 1;
+        let Some(stamp) = read_barcode(frame) else {
+            self.report.unreadable += 1;
+            return None;
+        };
+        self.record(stamp, observed_at);
+        Some(stamp)
+    }
+
+    pub fn record(&mut self, stamp: FrameStamp, observed_at: f64) {
+        let index = stamp.frame_index;
+        let report = &mut self.report;
+        report.first_index.get_or_insert(index);
+        report.last_index = Some(index);
+
+        let previous = self.previous.replace(stamp);
+        if previous.is_some_and(|previous| previous.frame_index == index) {
+            report.duplicated += 1;
+            return;
+        }
+
+        match self.newest {
+            Some(newest) if index <= newest => {
+                report.out_of_order += 1;
+                // Late, but it was shown after all
+                if self.missing.remove(&index) {
+                    report.dropped -= 1;
+                    report.unique_frames += 1;
+                }
+                return;
+            }
+            Some(newest) => {
+                report.dropped += (index - newest - 1) as usize;
+                let oldest = index.saturating_sub(LATE_WINDOW).max(newest + 1);
+                self.missing.extend(oldest..index);
+                self.missing = self.missing.split_off(&index.saturating_sub(LATE_WINDOW));
+            }
+            None => {}
+        }
+        self.newest = Some(index);
+        report.unique_frames += 1;
+
+        // Pacing only compares consecutive frames, so gaps don't count twice
+        if let Some((last, last_at)) = self.last_new.replace((stamp, observed_at)) {
+            let interval = observed_at - last_at;
+            self.interval_sum += interval;
+            self.intervals += 1;
+            if index == last.frame_index + 1 {
+                let expected = stamp.timestamp_ms as f64 - last.timestamp_ms as f64;
+                let error = interval - expected;
+                self.error_squares += error * error;
+                self.paced += 1;
+                report.max_pacing_error = report.max_pacing_error.max(error.abs());
+            }
+            report.mean_interval = self.interval_sum / self.intervals as f64;
+            if self.paced > 0 {
+                report.jitter = (self.error_squares / self.paced as f64).sqrt();
+            }
+        }
+    }
+
+    pub fn report(&self) -> &FrameAnalysisReport {
+        &self.report
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::queue::Queue;
+    use crate::decoder::signals::{embed_barcode, generate_signal, TestSignal};
+
+    fn stamped(index: u32) -> Frame {
+        let mut frame = generate_signal(TestSignal::MovingBox, 160, 90, index as u64);
+        embed_barcode(&mut frame, FrameStamp { frame_index: index, timestamp_ms: index * 40 });
+        frame
+    }
+
+    #[test]
+    fn test_queue_delivers_every_frame_in_order() {
+        let mut queue = Queue::new(32, 4);
+        for index in 0..20 {
+            queue.push(stamped(index));
+        }
+
+        let mut analyzer = FrameAnalyzer::new();
+        let mut shown_at = 0.0;
+        while queue.get_size() > 0 {
+            for frame in queue.process_batch() {
+                analyzer.observe(&frame, shown_at);
+                shown_at += 40.0;
+            }
+        }
+
+        let report = analyzer.report();
+        assert_eq!(report.unique_frames, 20);
+        assert_eq!((report.dropped, report.duplicated, report.out_of_order), (0, 0, 0));
+        assert_eq!((report.first_index, report.last_index), (Some(0), Some(19)));
+        assert_eq!(report.mean_interval, 40.0);
+        assert_eq!(report.jitter, 0.0);
+    }
+
+    #[test]
+    fn test_detects_drops_duplicates_and_reordering() {
+        let mut analyzer = FrameAnalyzer::new();
+        let shown = [0, 1, 1, 2, 5, 4, 6];
+        for (i, &index) in shown.iter().enumerate() {
+            analyzer.observe(&stamped(index), i as f64 * 40.0);
+        }
+        analyzer.observe(&Frame::new(160, 90), 300.0);
+
+        let report = analyzer.report();
+        assert_eq!(report.frames_observed, 8);
+        assert_eq!(report.unreadable, 1);
+        assert_eq!(report.duplicated, 1);
+        // 3 never arrived; 4 came late, so it's out of order rather than dropped
+        assert_eq!(report.dropped, 1);
+        assert_eq!(report.out_of_order, 1);
+        assert_eq!(report.unique_frames, 6);
+
+        // Showing an old frame again isn't a new one
+        analyzer.observe(&stamped(4), 320.0);
+        assert_eq!(analyzer.report().out_of_order, 2);
+        assert_eq!((analyzer.report().dropped, analyzer.report().unique_frames), (1, 6));
+    }
+
+    #[test]
+    fn test_pacing_jitter() {
+        let mut analyzer = FrameAnalyzer::new();
+        // Stamped 40ms apart, shown alternately 30ms and 50ms apart
+        for (index, at) in [(0, 0.0), (1, 30.0), (2, 80.0), (3, 110.0), (4, 160.0)] {
+            analyzer.record(FrameStamp { frame_index: index, timestamp_ms: index * 40 }, at);
+        }
+
+        let report = analyzer.report();
+        assert_eq!(report.mean_interval, 40.0);
+        assert_eq!(report.jitter, 10.0);
+        assert_eq!(report.max_pacing_error, 10.0);
+
+        analyzer.reset();
+        assert_eq!(analyzer.report(), &FrameAnalysisReport::default());
+    }
+}
@@ -0,0 +1,417 @@
+use serde::{Serialize, Deserialize};
+use super::frame::{Frame, PixelFormat};
+
+// CPU post-processing step. Filters take and return whole frames so they can
+// change the size; YUV input is converted to RGBA first.
+pub trait FrameFilter {
+    fn name(&self) -> &'static str;
+
+    fn apply(&mut self, frame: Frame) -> Result<Frame, String>;
+
+    // Forget state carried between frames (after a seek or flush)
+    fn reset(&mut self) {}
+}
+
+// Filters run in order, each on the previous one's output
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn FrameFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_configs(configs: &[FilterConfig]) -> Result<Self, String> {
+        let mut chain = Self::new();
+        for config in configs {
+            chain.push(config.build()?);
+        }
+        Ok(chain)
+    }
+
+    pub fn push(&mut self, filter: Box<dyn FrameFilter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.filters.iter().map(|filter| filter.name()).collect()
+    }
+
+    pub fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+        self.filters.iter_mut().try_fold(frame, |frame, filter| {
+            filter.apply(frame).map_err(|e| format!("{}: {}", filter.name(), e))
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(|filter| filter.reset());
+    }
+}
+
+// Serializable description of a filter, as passed from JS
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterConfig {
+    Sharpen { amount: f32 },
+    TemporalSmooth { strength: f32, scene_cut: f32 },
+    Levels { black: u8, white: u8, gamma: f32 },
+    EdgeAwareUpscale { factor: usize, edge_threshold: f32 },
+}
+
+impl FilterConfig {
+    pub fn build(&self) -> Result<Box<dyn FrameFilter>, String> {
+        Ok(match *self {
+            FilterConfig::Sharpen { amount } => Box::new(Sharpen::new(amount)?),
+            FilterConfig::TemporalSmooth { strength, scene_cut } => Box::new(TemporalSmooth::new(strength, scene_cut)?),
+            FilterConfig::Levels { black, white, gamma } => Box::new(Levels::new(black, white, gamma)?),
+            FilterConfig::EdgeAwareUpscale { factor, edge_threshold } => {
+                Box::new(EdgeAwareUpscale::new(factor, edge_threshold)?)
+            }
+        })
+    }
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<(), String> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be between {} and {}, got {}", name, min, max, value))
+    }
+}
+
+fn into_rgba(frame: Frame) -> Result<Frame, String> {
+    frame.validate()?;
+    match frame.format {
+        PixelFormat::Rgba => Ok(frame),
+        _ => frame.to_rgba(),
+    }
+}
+
+fn luma(pixel: &[u8]) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// Unsharp mask against a 3x3 binomial blur, edges clamped
+pub struct Sharpen {
+    amount: f32,
+}
+
+impl Sharpen {
+    pub fn new(amount: f32) -> Result<Self, String> {
+        check_range("amount", amount, 0.0, 5.0)?;
+        Ok(Self { amount })
+    }
+}
+
+impl FrameFilter for Sharpen {
+    fn name(&self) -> &'static str {
+        "sharpen"
+    }
+
+    fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+        let frame = into_rgba(frame)?;
+        let (width, height) = (frame.width, frame.height);
+        let mut output = frame.clone();
+        const KERNEL: [f32; 3] = [1.0, 2.0, 1.0];
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y * width + x) * 4;
+                for channel in 0..3 {
+                    let mut blur = 0.0;
+                    for (ky, wy) in KERNEL.iter().enumerate() {
+                        let sy = (y + ky).saturating_sub(1).min(height - 1);
+                        for (kx, wx) in KERNEL.iter().enumerate() {
+                            let sx = (x + kx).saturating_sub(1).min(width - 1);
+                            blur += frame.data[(sy * width + sx) * 4 + channel] as f32 * wx * wy;
+                        }
+                    }
+                    let center = frame.data[idx + channel] as f32;
+                    let value = center + self.amount * (center - blur / 16.0);
+                    output.data[idx + channel] = value.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+// Blends each frame with the previous output to hide frame-to-frame flicker.
+// Restarts on keyframes, size changes and scene cuts so motion doesn't ghost.
+pub struct TemporalSmooth {
+    strength: f32,
+    scene_cut: f32,
+    previous: Option<Frame>,
+}
+
+impl TemporalSmooth {
+    // `strength` is the weight of the previous output; `scene_cut` is the mean
+    // absolute difference (0-255) above which frames aren't blended
+    pub fn new(strength: f32, scene_cut: f32) -> Result<Self, String> {
+        check_range("strength", strength, 0.0, 0.95)?;
+        check_range("scene_cut", scene_cut, 0.0, 255.0)?;
+        Ok(Self { strength, scene_cut, previous: None })
+    }
+}
+
+impl FrameFilter for TemporalSmooth {
+    fn name(&self) -> &'static str {
+        "temporal_smooth"
+    }
+
+    fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+        let mut frame = into_rgba(frame)?;
+
+        let previous = self.previous.as_ref().filter(|previous| {
+            !frame.is_keyframe && previous.width == frame.width && previous.height == frame.height
+        });
+        if let Some(previous) = previous {
+            let difference = frame.data.iter().zip(&previous.data)
+                .map(|(&a, &b)| a.abs_diff(b) as f64)
+                .sum::<f64>() / frame.data.len().max(1) as f64;
+
+            if difference <= self.scene_cut as f64 {
+                for (value, &old) in frame.data.iter_mut().zip(&previous.data) {
+                    let blended = old as f32 * self.strength + *value as f32 * (1.0 - self.strength);
+                    *value = blended.round() as u8;
+                }
+            }
+        }
+
+        self.previous = Some(frame.clone());
+        Ok(frame)
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+// Maps [black, white] to [0, 255] with a gamma curve (gamma > 1 brightens)
+pub struct Levels {
+    table: [u8; 256],
+}
+
+impl Levels {
+    pub fn new(black: u8, white: u8, gamma: f32) -> Result<Self, String> {
+        if black >= white {
+            return Err(format!("black ({}) must be below white ({})", black, white));
+        }
+        check_range("gamma", gamma, 0.1, 10.0)?;
+
+        let mut table = [0u8; 256];
+        let span = (white - black) as f32;
+        for (value, entry) in table.iter_mut().enumerate() {
+            let normalized = ((value as f32 - black as f32) / span).clamp(0.0, 1.0);
+            *entry = (normalized.powf(1.0 / gamma) * 255.0).round() as u8;
+        }
+        Ok(Self { table })
+    }
+}
+
+impl FrameFilter for Levels {
+    fn name(&self) -> &'static str {
+        "levels"
+    }
+
+    fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+        let mut frame = into_rgba(frame)?;
+        for pixel in frame.data.chunks_exact_mut(4) {
+            for value in &mut pixel[..3] {
+                *value = self.table[*value as usize];
+            }
+        }
+        Ok(frame)
+    }
+}
+
+// Bilinear upscale by an integer factor where neighbours that differ from the
+// nearest source pixel by much more than `edge_threshold` (luma) lose weight,
+// so edges stay sharp instead of smearing
+pub struct EdgeAwareUpscale {
+    factor: usize,
+    edge_threshold: f32,
+}
+
+impl EdgeAwareUpscale {
+    pub fn new(factor: usize, edge_threshold: f32) -> Result<Self, String> {
+        if !(1..=8).contains(&factor) {
+            return Err(format!("factor must be between 1 and 8, got {}", factor));
+        }
+        check_range("edge_threshold", edge_threshold, 1.0, 255.0)?;
+        Ok(Self { factor, edge_threshold })
+    }
+}
+
+impl FrameFilter for EdgeAwareUpscale {
+    fn name(&self) -> &'static str {
+        "edge_aware_upscale"
+    }
+
+    fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+        let frame = into_rgba(frame)?;
+        if self.factor == 1 || frame.width == 0 || frame.height == 0 {
+            return Ok(frame);
+        }
+
+        let (width, height) = (frame.width, frame.height);
+        let mut output = Frame::new(width * self.factor, height * self.factor);
+        output.frame_index = frame.frame_index;
+        output.timestamp = frame.timestamp;
+        output.is_keyframe = frame.is_keyframe;
+
+        let pixel = |x: usize, y: usize| &frame.data[(y * width + x) * 4..(y * width + x) * 4 + 4];
+        let sigma = 2.0 * self.edge_threshold * self.edge_threshold;
+
+        for oy in 0..output.height {
+            let sy = ((oy as f32 + 0.5) / self.factor as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+            let (y0, fy) = (sy.floor() as usize, sy.fract());
+            let y1 = (y0 + 1).min(height - 1);
+
+            for ox in 0..output.width {
+                let sx = ((ox as f32 + 0.5) / self.factor as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+                let (x0, fx) = (sx.floor() as usize, sx.fract());
+                let x1 = (x0 + 1).min(width - 1);
+
+                let taps = [
+                    (pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+                    (pixel(x1, y0), fx * (1.0 - fy)),
+                    (pixel(x0, y1), (1.0 - fx) * fy),
+                    (pixel(x1, y1), fx * fy),
+                ];
+                let nearest = taps.iter()
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|tap| luma(tap.0))
+                    .unwrap_or(0.0);
+
+                let mut total = [0.0f32; 4];
+                let mut weights = 0.0;
+                for (tap, weight) in taps {
+                    let difference = luma(tap) - nearest;
+                    let weight = weight * (-difference * difference / sigma).exp();
+                    for (sum, &value) in total.iter_mut().zip(tap) {
+                        *sum += value as f32 * weight;
+                    }
+                    weights += weight;
+                }
+
+                let idx = (oy * output.width + ox) * 4;
+                for (channel, sum) in total.iter().enumerate() {
+                    output.data[idx + channel] = (sum / weights).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_from(width: usize, height: usize, pixels: &[u8]) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.set_data(pixels.iter().flat_map(|&value| [value, value, value, 255]).collect());
+        frame
+    }
+
+    fn gray(frame: &Frame) -> Vec<u8> {
+        frame.data.chunks(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn test_sharpen_boosts_edges_and_keeps_flat_areas() {
+        let mut sharpen = Sharpen::new(1.0).unwrap();
+
+        let flat = sharpen.apply(frame_from(3, 1, &[100, 100, 100])).unwrap();
+        assert_eq!(gray(&flat), vec![100, 100, 100]);
+
+        let edge = sharpen.apply(frame_from(4, 1, &[50, 50, 150, 150])).unwrap();
+        let values = gray(&edge);
+        assert!(values[1] < 50 && values[2] > 150);
+        assert!(edge.data.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn test_temporal_smooth_blends_and_resets() {
+        let mut smooth = TemporalSmooth::new(0.5, 64.0).unwrap();
+        smooth.apply(frame_from(2, 1, &[100, 100])).unwrap();
+
+        let blended = smooth.apply(frame_from(2, 1, &[120, 80])).unwrap();
+        assert_eq!(gray(&blended), vec![110, 90]);
+
+        // Scene cut: too different to blend
+        let cut = smooth.apply(frame_from(2, 1, &[250, 0])).unwrap();
+        assert_eq!(gray(&cut), vec![250, 0]);
+
+        let mut keyframe = frame_from(2, 1, &[200, 10]);
+        keyframe.is_keyframe = true;
+        assert_eq!(gray(&smooth.apply(keyframe).unwrap()), vec![200, 10]);
+
+        smooth.reset();
+        assert_eq!(gray(&smooth.apply(frame_from(2, 1, &[0, 0])).unwrap()), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_levels() {
+        let mut levels = Levels::new(16, 235, 1.0).unwrap();
+        let output = levels.apply(frame_from(3, 1, &[16, 235, 125])).unwrap();
+        assert_eq!(gray(&output), vec![0, 255, 127]);
+        assert_eq!(output.data[3], 255);
+
+        let mut brighten = Levels::new(0, 255, 2.0).unwrap();
+        assert!(gray(&brighten.apply(frame_from(1, 1, &[64])).unwrap())[0] > 64);
+
+        assert!(Levels::new(200, 100, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_edge_aware_upscale_keeps_hard_edges() {
+        let mut upscale = EdgeAwareUpscale::new(2, 8.0).unwrap();
+        let output = upscale.apply(frame_from(2, 1, &[0, 255])).unwrap();
+        assert_eq!((output.width, output.height), (4, 2));
+
+        // Plain bilinear would put 64 and 191 in the middle columns
+        assert_eq!(gray(&output), vec![0, 0, 255, 255, 0, 0, 255, 255]);
+
+        let smooth = upscale.apply(frame_from(2, 1, &[100, 104])).unwrap();
+        assert_eq!(gray(&smooth)[..4], [100, 101, 103, 104]);
+    }
+
+    #[test]
+    fn test_chain_order_and_errors() {
+        let configs = [
+            FilterConfig::Levels { black: 0, white: 127, gamma: 1.0 },
+            FilterConfig::EdgeAwareUpscale { factor: 2, edge_threshold: 16.0 },
+        ];
+        let mut chain = FilterChain::from_configs(&configs).unwrap();
+        assert_eq!(chain.names(), vec!["levels", "edge_aware_upscale"]);
+
+        let output = chain.apply(frame_from(1, 1, &[100])).unwrap();
+        assert_eq!((output.width, output.height), (2, 2));
+        assert_eq!(gray(&output), vec![201; 4]);
+
+        let mut broken = Frame::new(2, 2);
+        broken.data.pop();
+        let error = chain.apply(broken).err().unwrap();
+        assert!(error.starts_with("levels:"));
+
+        assert!(FilterChain::from_configs(&[FilterConfig::Sharpen { amount: 9.0 }]).is_err());
+    }
+
+    #[test]
+    fn test_yuv_input_is_converted() {
+        let mut frame = Frame::with_format(2, 2, PixelFormat::I420);
+        frame.set_data(vec![235, 235, 235, 235, 128, 128]);
+
+        let output = Levels::new(0, 255, 1.0).unwrap().apply(frame).unwrap();
+        assert_eq!(output.format, PixelFormat::Rgba);
+        assert!(output.data.iter().all(|&value| value == 255));
+    }
+}
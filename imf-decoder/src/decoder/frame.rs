@@ -1,27 +1,280 @@
 use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
+
+// How `Frame::data` is laid out. YUV formats are 4:2:0 with the chroma planes
+// rounded up for odd sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PixelFormat {
+    // Interleaved 8-bit RGBA
+    #[default]
+    Rgba,
+    // Y plane, then U, then V
+    I420,
+    // Y plane, then interleaved UV
+    Nv12,
+}
+
+// Y, U and V sample planes borrowed from a frame
+pub type YuvPlanes<'a> = (&'a [u8], &'a [u8], &'a [u8]);
+
+// YUV -> RGB matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorMatrix {
+    #[default]
+    Bt601,
+    Bt709,
+}
+
+// Limited is 16-235 luma / 16-240 chroma, full uses 0-255
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorRange {
+    #[default]
+    Limited,
+    Full,
+}
+
+impl PixelFormat {
+    // Size of one chroma plane (in samples) for a width x height frame
+    pub fn chroma_size(width: usize, height: usize) -> (usize, usize) {
+        (width.div_ceil(2), height.div_ceil(2))
+    }
+
+    pub fn data_len(&self, width: usize, height: usize) -> usize {
+        let (chroma_width, chroma_height) = Self::chroma_size(width, height);
+        match self {
+            PixelFormat::Rgba => width * height * 4,
+            PixelFormat::I420 | PixelFormat::Nv12 => width * height + chroma_width * chroma_height * 2,
+        }
+    }
+}
+
+// YUV -> RGB conversion in normalized units: subtract `offset` from
+// (Y, U, V), then multiply by `matrix` (rows produce R, G, B)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YuvTransform {
+    pub matrix: [[f32; 3]; 3],
+    pub offset: [f32; 3],
+}
+
+impl YuvTransform {
+    pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        let (kr, kb) = match matrix {
+            ColorMatrix::Bt601 => (0.299, 0.114),
+            ColorMatrix::Bt709 => (0.2126, 0.0722),
+        };
+        let kg = 1.0 - kr - kb;
+
+        let (luma_offset, luma_scale, chroma_scale) = match range {
+            ColorRange::Limited => (16.0 / 255.0, 255.0 / 219.0, 255.0 / 224.0),
+            ColorRange::Full => (0.0, 1.0, 1.0),
+        };
+
+        let y = luma_scale;
+        let u = chroma_scale;
+        Self {
+            matrix: [
+                [y, 0.0, 2.0 * (1.0 - kr) * u],
+                [y, -2.0 * kb * (1.0 - kb) / kg * u, -2.0 * kr * (1.0 - kr) / kg * u],
+                [y, 2.0 * (1.0 - kb) * u, 0.0],
+            ],
+            offset: [luma_offset, 128.0 / 255.0, 128.0 / 255.0],
+        }
+    }
+
+    pub fn apply(&self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let yuv = [
+            y as f32 / 255.0 - self.offset[0],
+            u as f32 / 255.0 - self.offset[1],
+            v as f32 / 255.0 - self.offset[2],
+        ];
+        self.matrix.map(|row| {
+            let value = row[0] * yuv[0] + row[1] * yuv[1] + row[2] * yuv[2];
+            (value * 255.0).round().clamp(0.0, 255.0) as u8
+        })
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Frame {
     pub width: usize,
     pub height: usize,
     pub data: Vec<u8>,
+    // Position in the stream; PTS alone can't give it once the frame rate changes
+    #[serde(default)]
+    pub frame_index: usize,
     pub timestamp: f64,
     pub is_keyframe: bool,
+    #[serde(default)]
+    pub format: PixelFormat,
+    #[serde(default)]
+    pub matrix: ColorMatrix,
+    #[serde(default)]
+    pub range: ColorRange,
 }
 
 impl Frame {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_format(width, height, PixelFormat::Rgba)
+    }
+
+    pub fn with_format(width: usize, height: usize, format: PixelFormat) -> Self {
         Self {
             width,
             height,
-            data: vec![0; width * height * 4],
+            data: vec![0; format.data_len(width, height)],
+            frame_index: 0,
             timestamp: 0.0,
             is_keyframe: false,
+            format,
+            matrix: ColorMatrix::default(),
+            range: ColorRange::default(),
         }
     }
 
     pub fn set_data(&mut self, data: Vec<u8>) {
-        assert_eq!(data.len(), self.width * self.height * 4);
+        assert_eq!(data.len(), self.format.data_len(self.width, self.height));
         self.data = data;
     }
-}
\ No newline at end of file
+
+    pub fn is_valid(&self) -> bool {
+        self.data.len() == self.format.data_len(self.width, self.height)
+    }
+
+    // Same check as `is_valid`, describing the mismatch
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_valid() {
+            return Ok(());
+        }
+        Err(format!(
+            "Frame data is {} bytes, expected {} for {}x{} {:?}",
+            self.data.len(), self.format.data_len(self.width, self.height),
+            self.width, self.height, self.format
+        ))
+    }
+
+    // Splits YUV data into (Y, U, V) planes; NV12 returns the interleaved UV
+    // plane as U and an empty V
+    pub fn yuv_planes(&self) -> Result<YuvPlanes<'_>, String> {
+        self.validate()?;
+        let luma = self.width * self.height;
+        let (chroma_width, chroma_height) = PixelFormat::chroma_size(self.width, self.height);
+        let chroma = chroma_width * chroma_height;
+        let (y, rest) = self.data.split_at(luma);
+        Ok(match self.format {
+            PixelFormat::Rgba => (&[], &[], &[]),
+            PixelFormat::I420 => {
+                let (u, v) = rest.split_at(chroma);
+                (y, u, v)
+            }
+            PixelFormat::Nv12 => (y, rest, &[]),
+        })
+    }
+
+    // Borrows RGBA frames and converts anything else. Errors when the data
+    // doesn't match the size and format.
+    pub fn as_rgba(&self) -> Result<Cow<'_, Frame>, String> {
+        match self.format {
+            PixelFormat::Rgba => self.validate().map(|_| Cow::Borrowed(self)),
+            _ => self.to_rgba().map(Cow::Owned),
+        }
+    }
+
+    // Same frame as interleaved RGBA, converting YUV on the CPU
+    pub fn to_rgba(&self) -> Result<Frame, String> {
+        if self.format == PixelFormat::Rgba {
+            self.validate()?;
+            return Ok(self.clone());
+        }
+
+        let transform = YuvTransform::new(self.matrix, self.range);
+        let (chroma_width, _) = PixelFormat::chroma_size(self.width, self.height);
+        let (y_plane, u_plane, v_plane) = self.yuv_planes()?;
+
+        let mut rgba = Frame::new(self.width, self.height);
+        rgba.frame_index = self.frame_index;
+        rgba.timestamp = self.timestamp;
+        rgba.is_keyframe = self.is_keyframe;
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let chroma = (row / 2) * chroma_width + col / 2;
+                let (u, v) = match self.format {
+                    PixelFormat::Nv12 => (u_plane[chroma * 2], u_plane[chroma * 2 + 1]),
+                    _ => (u_plane[chroma], v_plane[chroma]),
+                };
+                let [r, g, b] = transform.apply(y_plane[row * self.width + col], u, v);
+
+                let idx = (row * self.width + col) * 4;
+                rgba.data[idx..idx + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+        Ok(rgba)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_len_per_format() {
+        assert_eq!(PixelFormat::Rgba.data_len(4, 2), 32);
+        assert_eq!(PixelFormat::I420.data_len(4, 2), 8 + 2 * 2);
+        // Odd sizes round the chroma planes up
+        assert_eq!(PixelFormat::Nv12.data_len(3, 3), 9 + 2 * 2 * 2);
+        assert!(Frame::with_format(5, 3, PixelFormat::I420).is_valid());
+    }
+
+    #[test]
+    fn test_limited_range_reference_points() {
+        for matrix in [ColorMatrix::Bt601, ColorMatrix::Bt709] {
+            let transform = YuvTransform::new(matrix, ColorRange::Limited);
+            assert_eq!(transform.apply(16, 128, 128), [0, 0, 0]);
+            assert_eq!(transform.apply(235, 128, 128), [255, 255, 255]);
+        }
+
+        let full = YuvTransform::new(ColorMatrix::Bt601, ColorRange::Full);
+        assert_eq!(full.apply(0, 128, 128), [0, 0, 0]);
+        assert_eq!(full.apply(255, 128, 128), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_matrices_differ_on_saturated_colors() {
+        // Full-range red encoded with BT.601
+        let (y, u, v) = (76, 85, 255);
+        let bt601 = YuvTransform::new(ColorMatrix::Bt601, ColorRange::Full).apply(y, u, v);
+        let bt709 = YuvTransform::new(ColorMatrix::Bt709, ColorRange::Full).apply(y, u, v);
+
+        assert!(bt601[0] >= 254 && bt601[1] <= 1 && bt601[2] <= 1);
+        assert_ne!(bt601, bt709);
+    }
+
+    #[test]
+    fn test_i420_and_nv12_convert_the_same() {
+        let luma = vec![10, 60, 110, 160, 30, 80, 130, 180];
+
+        let mut i420 = Frame::with_format(4, 2, PixelFormat::I420);
+        i420.range = ColorRange::Full;
+        i420.set_data([luma.clone(), vec![90, 40], vec![200, 150]].concat());
+
+        let mut nv12 = Frame::with_format(4, 2, PixelFormat::Nv12);
+        nv12.range = ColorRange::Full;
+        nv12.set_data([luma, vec![90, 200, 40, 150]].concat());
+
+        let rgba = i420.to_rgba().unwrap();
+        assert_eq!(rgba.data, nv12.to_rgba().unwrap().data);
+        assert_eq!(rgba.format, PixelFormat::Rgba);
+        assert!(rgba.data.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn test_truncated_frames_fail_to_convert() {
+        for format in [PixelFormat::I420, PixelFormat::Nv12, PixelFormat::Rgba] {
+            let mut frame = Frame::with_format(4, 2, format);
+            frame.data.truncate(5);
+            assert!(frame.yuv_planes().is_err());
+            assert!(frame.to_rgba().is_err());
+            assert!(frame.as_rgba().is_err());
+        }
+    }
+}
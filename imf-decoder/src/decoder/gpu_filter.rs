@@ -0,0 +1,232 @@
+use serde::{Serialize, Deserialize};
+
+// One post-processing pass run by WebGLDecoder, in chain order. Passes read
+// the previous output; BicubicUpscale switches the rest of the chain to
+// display resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GpuFilter {
+    // Adds `amount` x (image - blur); `radius` in source pixels
+    UnsharpMask {
+        #[serde(default = "default_amount")]
+        amount: f32,
+        #[serde(default = "default_radius")]
+        radius: f32,
+    },
+    // AMD CAS style: sharpens less where local contrast is already high
+    ContrastAdaptiveSharpen {
+        #[serde(default = "default_sharpness")]
+        sharpness: f32,
+    },
+    // 3x3 bilateral blur; higher strength smooths larger differences
+    Denoise {
+        #[serde(default = "default_strength")]
+        strength: f32,
+    },
+    // Mitchell-Netravali cubic, Catmull-Rom by default
+    BicubicUpscale {
+        #[serde(default)]
+        b: f32,
+        #[serde(default = "default_c")]
+        c: f32,
+    },
+}
+
+fn default_amount() -> f32 { 1.0 }
+fn default_radius() -> f32 { 1.0 }
+fn default_sharpness() -> f32 { 0.5 }
+fn default_strength() -> f32 { 0.3 }
+fn default_c() -> f32 { 0.5 }
+
+impl GpuFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, value: f32, min: f32, max: f32| {
+            if (min..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!("{:?}: {} must be between {} and {}, got {}", self, name, min, max, value))
+            }
+        };
+
+        match *self {
+            GpuFilter::UnsharpMask { amount, radius } => {
+                check("amount", amount, 0.0, 5.0)?;
+                check("radius", radius, 0.5, 4.0)
+            }
+            GpuFilter::ContrastAdaptiveSharpen { sharpness } => check("sharpness", sharpness, 0.0, 1.0),
+            GpuFilter::Denoise { strength } => check("strength", strength, 0.0, 1.0),
+            GpuFilter::BicubicUpscale { b, c } => {
+                check("b", b, 0.0, 1.0)?;
+                check("c", c, 0.0, 1.0)
+            }
+        }
+    }
+
+    // Whether the pass renders at display size rather than its input size
+    pub fn upscales(&self) -> bool {
+        matches!(self, GpuFilter::BicubicUpscale { .. })
+    }
+
+    // Values for the pass's `params` uniform
+    pub fn params(&self) -> [f32; 4] {
+        match *self {
+            GpuFilter::UnsharpMask { amount, radius } => [amount, radius, 0.0, 0.0],
+            GpuFilter::ContrastAdaptiveSharpen { sharpness } => [sharpness, 0.0, 0.0, 0.0],
+            GpuFilter::Denoise { strength } => [strength, 0.0, 0.0, 0.0],
+            GpuFilter::BicubicUpscale { b, c } => [b, c, 0.0, 0.0],
+        }
+    }
+
+    pub fn fragment_shader(&self) -> &'static str {
+        match self {
+            GpuFilter::UnsharpMask { .. } => UNSHARP_MASK_SHADER,
+            GpuFilter::ContrastAdaptiveSharpen { .. } => CAS_SHADER,
+            GpuFilter::Denoise { .. } => DENOISE_SHADER,
+            GpuFilter::BicubicUpscale { .. } => BICUBIC_SHADER,
+        }
+    }
+}
+
+// Filter passes sample textures rendered by earlier passes, which are
+// already bottom-up, so unlike the frame shaders this doesn't flip
+pub const FILTER_VERTEX_SHADER: &str = r#"#version 300 es
+out vec2 uv;
+void main() {
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    uv = corner;
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const UNSHARP_MASK_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D source;
+uniform vec2 texel;
+uniform vec4 params;
+in vec2 uv;
+out vec4 outColor;
+void main() {
+    vec4 center = texture(source, uv);
+    vec2 offset = texel * params.y;
+    vec4 blur = center * 4.0;
+    blur += (texture(source, uv + vec2(offset.x, 0.0)) + texture(source, uv - vec2(offset.x, 0.0))
+           + texture(source, uv + vec2(0.0, offset.y)) + texture(source, uv - vec2(0.0, offset.y))) * 2.0;
+    blur += texture(source, uv + offset) + texture(source, uv - offset)
+          + texture(source, uv + vec2(offset.x, -offset.y)) + texture(source, uv + vec2(-offset.x, offset.y));
+    blur /= 16.0;
+    outColor = vec4(clamp(center.rgb + params.x * (center.rgb - blur.rgb), 0.0, 1.0), center.a);
+}
+"#;
+
+const CAS_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D source;
+uniform vec2 texel;
+uniform vec4 params;
+in vec2 uv;
+out vec4 outColor;
+void main() {
+    vec4 center = texture(source, uv);
+    vec3 n = texture(source, uv + vec2(0.0, texel.y)).rgb;
+    vec3 s = texture(source, uv - vec2(0.0, texel.y)).rgb;
+    vec3 e = texture(source, uv + vec2(texel.x, 0.0)).rgb;
+    vec3 w = texture(source, uv - vec2(texel.x, 0.0)).rgb;
+
+    vec3 lo = min(center.rgb, min(min(n, s), min(e, w)));
+    vec3 hi = max(center.rgb, max(max(n, s), max(e, w)));
+    vec3 amp = sqrt(clamp(min(lo, 1.0 - hi) / max(hi, 1e-5), 0.0, 1.0));
+    vec3 weight = amp * (-1.0 / mix(8.0, 5.0, params.x));
+
+    vec3 color = (center.rgb + (n + s + e + w) * weight) / (1.0 + 4.0 * weight);
+    outColor = vec4(clamp(color, 0.0, 1.0), center.a);
+}
+"#;
+
+const DENOISE_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D source;
+uniform vec2 texel;
+uniform vec4 params;
+in vec2 uv;
+out vec4 outColor;
+void main() {
+    vec4 center = texture(source, uv);
+    float sigma = max(params.x * 0.25, 1e-3);
+    vec3 total = vec3(0.0);
+    float weights = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec3 sample_ = texture(source, uv + vec2(x, y) * texel).rgb;
+            vec3 diff = sample_ - center.rgb;
+            float weight = exp(-dot(diff, diff) / (2.0 * sigma * sigma)) * (x == 0 && y == 0 ? 1.0 : 0.5);
+            total += sample_ * weight;
+            weights += weight;
+        }
+    }
+    outColor = vec4(total / weights, center.a);
+}
+"#;
+
+const BICUBIC_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D source;
+uniform vec2 texel;
+uniform vec4 params;
+in vec2 uv;
+out vec4 outColor;
+
+float cubic(float x) {
+    float b = params.x;
+    float c = params.y;
+    x = abs(x);
+    if (x < 1.0) {
+        return ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0;
+    }
+    if (x < 2.0) {
+        return ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0;
+    }
+    return 0.0;
+}
+
+void main() {
+    vec2 position = uv / texel - 0.5;
+    vec2 base = floor(position);
+    vec2 f = position - base;
+
+    vec4 total = vec4(0.0);
+    float weights = 0.0;
+    for (int y = -1; y <= 2; y++) {
+        for (int x = -1; x <= 2; x++) {
+            float weight = cubic(float(x) - f.x) * cubic(float(y) - f.y);
+            total += texture(source, (base + vec2(x, y) + 0.5) * texel) * weight;
+            weights += weight;
+        }
+    }
+    outColor = clamp(total / weights, 0.0, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_ranges() {
+        assert!(GpuFilter::UnsharpMask { amount: 1.5, radius: 1.0 }.validate().is_ok());
+        assert!(GpuFilter::UnsharpMask { amount: 6.0, radius: 1.0 }.validate().is_err());
+        assert!(GpuFilter::ContrastAdaptiveSharpen { sharpness: -0.1 }.validate().is_err());
+        assert!(GpuFilter::Denoise { strength: f32::NAN }.validate().is_err());
+        assert!(GpuFilter::BicubicUpscale { b: 1.0 / 3.0, c: 1.0 / 3.0 }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_params_and_upscale_flag() {
+        let upscale = GpuFilter::BicubicUpscale { b: 0.0, c: 0.5 };
+        assert!(upscale.upscales());
+        assert_eq!(upscale.params(), [0.0, 0.5, 0.0, 0.0]);
+
+        let sharpen = GpuFilter::UnsharpMask { amount: 2.0, radius: 1.5 };
+        assert!(!sharpen.upscales());
+        assert_eq!(sharpen.params(), [2.0, 1.5, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use super::protocol::DecoderStatus;
+
+// What can happen to a DecoderWorker; `DecoderStatus::apply` says where each
+// one leads from each status
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderEvent {
+    Initialize,
+    Initialized,
+    Prepared,
+    Start,
+    Pause,
+    Close,
+    Fail,
+    Reset,
+}
+
+impl DecoderEvent {
+    pub const ALL: [DecoderEvent; 8] = [
+        DecoderEvent::Initialize,
+        DecoderEvent::Initialized,
+        DecoderEvent::Prepared,
+        DecoderEvent::Start,
+        DecoderEvent::Pause,
+        DecoderEvent::Close,
+        DecoderEvent::Fail,
+        DecoderEvent::Reset,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    pub from: DecoderStatus,
+    pub event: DecoderEvent,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} isn't allowed while the decoder is {:?}", self.event, self.from)
+    }
+}
+
+impl DecoderStatus {
+    pub const ALL: [DecoderStatus; 8] = [
+        DecoderStatus::Idle,
+        DecoderStatus::Initializing,
+        DecoderStatus::Inited,
+        DecoderStatus::Ready,
+        DecoderStatus::Open,
+        DecoderStatus::Pause,
+        DecoderStatus::Closed,
+        DecoderStatus::Error,
+    ];
+
+    //   Idle --Initialize--> Initializing --Initialized--> Inited --Prepared--> Ready
+    //   Ready / Pause --Start--> Open --Pause--> Pause
+    //   Inited / Ready / Open / Pause / Error --Close--> Closed
+    //   anything started but not closed --Fail--> Error
+    //   anything --Reset--> Idle
+    pub fn apply(self, event: DecoderEvent) -> Result<DecoderStatus, TransitionError> {
+        use DecoderEvent as E;
+        use DecoderStatus as S;
+
+        let next = match (self, event) {
+            (_, E::Reset) => S::Idle,
+            (S::Idle, E::Initialize) => S::Initializing,
+            (S::Initializing, E::Initialized) => S::Inited,
+            (S::Inited, E::Prepared) => S::Ready,
+            (S::Ready | S::Pause, E::Start) => S::Open,
+            (S::Open, E::Pause) => S::Pause,
+            (S::Inited | S::Ready | S::Open | S::Pause | S::Error, E::Close) => S::Closed,
+            (S::Initializing | S::Inited | S::Ready | S::Open | S::Pause, E::Fail) => S::Error,
+            _ => return Err(TransitionError { from: self, event }),
+        };
+        Ok(next)
+    }
+
+    // Whether tokens can be decoded in this status
+    pub fn is_decoding(self) -> bool {
+        self == DecoderStatus::Open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DecoderEvent as E;
+    use DecoderStatus as S;
+
+    // Every legal transition; anything not listed must be rejected
+    const LEGAL: &[(DecoderStatus, DecoderEvent, DecoderStatus)] = &[
+        (S::Idle, E::Initialize, S::Initializing),
+        (S::Initializing, E::Initialized, S::Inited),
+        (S::Initializing, E::Fail, S::Error),
+        (S::Inited, E::Prepared, S::Ready),
+        (S::Inited, E::Close, S::Closed),
+        (S::Inited, E::Fail, S::Error),
+        (S::Ready, E::Start, S::Open),
+        (S::Ready, E::Close, S::Closed),
+        (S::Ready, E::Fail, S::Error),
+        (S::Open, E::Pause, S::Pause),
+        (S::Open, E::Close, S::Closed),
+        (S::Open, E::Fail, S::Error),
+        (S::Pause, E::Start, S::Open),
+        (S::Pause, E::Close, S::Closed),
+        (S::Pause, E::Fail, S::Error),
+        (S::Error, E::Close, S::Closed),
+    ];
+
+    #[test]
+    fn test_transition_table_is_exhaustive() {
+        for from in S::ALL {
+            for event in E::ALL {
+                let expected = if event == E::Reset {
+                    Some(S::Idle)
+                } else {
+                    LEGAL.iter().find(|(f, e, _)| *f == from && *e == event).map(|(_, _, to)| *to)
+                };
+
+                match expected {
+                    Some(to) => assert_eq!(from.apply(event), Ok(to), "{:?} + {:?}", from, event),
+                    None => assert_eq!(from.apply(event), Err(TransitionError { from, event })),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_lifecycle() {
+        let events = [E::Initialize, E::Initialized, E::Prepared, E::Start, E::Pause, E::Start, E::Close, E::Reset];
+        let status = events.iter().try_fold(S::Idle, |status, &event| status.apply(event)).unwrap();
+        assert_eq!(status, S::Idle);
+
+        // A failed open can only be closed or reset
+        let failed = S::Initializing.apply(E::Fail).unwrap();
+        assert!(failed.apply(E::Start).is_err());
+        assert_eq!(failed.apply(E::Close), Ok(S::Closed));
+        assert!(S::Closed.apply(E::Start).is_err());
+    }
+
+    #[test]
+    fn test_error_message() {
+        let error = S::Inited.apply(E::Start).unwrap_err();
+        assert_eq!(error.to_string(), "Start isn't allowed while the decoder is Inited");
+    }
+}
@@ -1,9 +1,34 @@
+pub mod adaptive;
+pub mod analyzer;
+pub mod filter;
 pub mod frame;
+pub mod gpu_filter;
+pub mod lifecycle;
+pub mod playback;
+pub mod protocol;
 pub mod queue;
+pub mod scheduler;
+pub mod signals;
+pub mod stream;
 pub mod tensor;
 pub mod webgl;
+pub mod worker;
 
-pub use frame::Frame;
-pub use queue::Queue;
+pub use adaptive::{AdaptiveConfig, AdaptiveRateController, RateChange, RateChangeReason};
+pub use analyzer::{FrameAnalysisReport, FrameAnalyzer};
+pub use filter::{FilterChain, FilterConfig, FrameFilter};
+pub use frame::{ColorMatrix, ColorRange, Frame, PixelFormat, YuvTransform};
+pub use gpu_filter::GpuFilter;
+pub use lifecycle::{DecoderEvent, TransitionError};
+pub use playback::{FrameSequencer, PlaybackMode, Step};
+pub use protocol::{
+    DecodeMessage, DecoderOptions, DecoderSettings, DecoderStatus, ErrorDetails, ErrorKind, FramePayload, StatusSnapshot,
+    TokenPacket, WorkerMessage, PROTOCOL_VERSION,
+};
+pub use queue::{Queue, QueueStats};
+pub use scheduler::{Presentation, PresentationScheduler, PresentationStats};
+pub use signals::{embed_barcode, generate_signal, read_barcode, FrameStamp, TestSignal};
+pub use stream::{decode_token, quantize_token, quantized_token_bits, TokenStream};
 pub use tensor::Tensor;
 pub use webgl::WebGLDecoder;
+pub use worker::DecoderWorker;
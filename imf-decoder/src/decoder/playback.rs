@@ -0,0 +1,287 @@
+use wasm_bindgen::prelude::*;
+use serde::{Serialize, Deserialize};
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    Once = 0,
+    Loop = 1,
+    PingPong = 2,
+    ReverseLoop = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    // Moved to the next frame in the current direction
+    Frame(usize),
+    // Wrapped around to the other end (loop modes)
+    Looped(usize),
+    // Turned around at either end (ping-pong)
+    Reversed(usize),
+    // Play-once ran past the last frame; position stays on it
+    Ended,
+    // Nothing to advance to (no frames, or a single looping frame)
+    Hold,
+}
+
+impl Step {
+    // Name of the playback event this step raises, if any
+    pub fn event_name(&self) -> Option<&'static str> {
+        match self {
+            Step::Looped(_) => Some("looped"),
+            Step::Reversed(_) => Some("reversed"),
+            Step::Ended => Some("ended"),
+            Step::Frame(_) | Step::Hold => None,
+        }
+    }
+}
+
+// Walks frame indices for a PlaybackMode, independent of how frames are drawn
+#[derive(Debug, Clone)]
+pub struct FrameSequencer {
+    mode: PlaybackMode,
+    position: usize,
+    direction: i32,  // As the mode dictates
+    reversed: bool,  // Negative playback rate, which flips the mode's direction
+    ended: bool,
+}
+
+impl FrameSequencer {
+    pub fn new(mode: PlaybackMode) -> Self {
+        let mut sequencer = Self {
+            mode,
+            position: 0,
+            direction: 1,
+            reversed: false,
+            ended: false,
+        };
+        sequencer.set_mode(mode);
+        sequencer
+    }
+
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        self.mode = mode;
+        self.ended = false;
+        match mode {
+            PlaybackMode::Once | PlaybackMode::Loop => self.direction = 1,
+            PlaybackMode::ReverseLoop => self.direction = -1,
+            PlaybackMode::PingPong => {}
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.position
+    }
+
+    // The way playback actually moves: the mode's direction times the sign
+    // of the rate
+    pub fn direction(&self) -> i32 {
+        if self.reversed { -self.direction } else { self.direction }
+    }
+
+    pub fn set_direction(&mut self, direction: i32) {
+        self.direction = if (direction < 0) != self.reversed { -1 } else { 1 };
+        self.ended = false;
+    }
+
+    pub fn set_reversed(&mut self, reversed: bool) {
+        if reversed != self.reversed {
+            self.reversed = reversed;
+            self.ended = false;
+        }
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    // Moves to the first frame for the current direction
+    pub fn rewind(&mut self, len: usize) {
+        self.position = if self.direction() < 0 { len.saturating_sub(1) } else { 0 };
+        self.ended = false;
+    }
+
+    // Jumps to `index`, clamped to the sequence
+    pub fn seek(&mut self, index: usize, len: usize) {
+        self.position = index.min(len.saturating_sub(1));
+        self.ended = false;
+    }
+
+    pub fn step(&mut self, len: usize) -> Step {
+        if len == 0 {
+            self.position = 0;
+            return Step::Hold;
+        }
+        if self.ended {
+            return Step::Ended;
+        }
+
+        self.position = self.position.min(len - 1);
+        if let Some(next) = self.offset(self.direction(), len) {
+            self.position = next;
+            return Step::Frame(next);
+        }
+
+        match self.mode {
+            PlaybackMode::Once => {
+                self.ended = true;
+                Step::Ended
+            }
+            PlaybackMode::Loop | PlaybackMode::ReverseLoop => {
+                if len == 1 {
+                    return Step::Hold;
+                }
+                self.position = if self.direction() > 0 { 0 } else { len - 1 };
+                Step::Looped(self.position)
+            }
+            PlaybackMode::PingPong => {
+                self.direction = -self.direction;
+                match self.offset(self.direction(), len) {
+                    Some(next) => {
+                        self.position = next;
+                        Step::Reversed(next)
+                    }
+                    None => Step::Hold,
+                }
+            }
+        }
+    }
+
+    // Index the next step would move to, without moving
+    pub fn peek(&self, len: usize) -> Option<usize> {
+        match self.clone().step(len) {
+            Step::Frame(index) | Step::Looped(index) | Step::Reversed(index) => Some(index),
+            Step::Ended | Step::Hold => None,
+        }
+    }
+
+    fn offset(&self, direction: i32, len: usize) -> Option<usize> {
+        let next = self.position as i64 + direction as i64;
+        (0..len as i64).contains(&next).then_some(next as usize)
+    }
+}
+
+impl Default for FrameSequencer {
+    fn default() -> Self {
+        Self::new(PlaybackMode::PingPong)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Current index followed by the index after each of `steps` steps
+    fn walk(mode: PlaybackMode, len: usize, steps: usize) -> Vec<usize> {
+        let mut sequencer = FrameSequencer::new(mode);
+        sequencer.rewind(len);
+        let mut indices = vec![sequencer.current()];
+        for _ in 0..steps {
+            sequencer.step(len);
+            indices.push(sequencer.current());
+        }
+        indices
+    }
+
+    #[test]
+    fn test_mode_sequences() {
+        assert_eq!(walk(PlaybackMode::Once, 3, 5), vec![0, 1, 2, 2, 2, 2]);
+        assert_eq!(walk(PlaybackMode::Loop, 3, 5), vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(walk(PlaybackMode::PingPong, 3, 6), vec![0, 1, 2, 1, 0, 1, 2]);
+        assert_eq!(walk(PlaybackMode::ReverseLoop, 3, 5), vec![2, 1, 0, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_short_sequences() {
+        for mode in [PlaybackMode::Once, PlaybackMode::Loop, PlaybackMode::PingPong, PlaybackMode::ReverseLoop] {
+            assert_eq!(walk(mode, 0, 3), vec![0, 0, 0, 0]);
+            assert_eq!(walk(mode, 1, 3), vec![0, 0, 0, 0]);
+        }
+        assert_eq!(walk(PlaybackMode::PingPong, 2, 4), vec![0, 1, 0, 1, 0]);
+        assert_eq!(walk(PlaybackMode::Loop, 2, 3), vec![0, 1, 0, 1]);
+        assert_eq!(walk(PlaybackMode::ReverseLoop, 2, 3), vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_step_events() {
+        let mut once = FrameSequencer::new(PlaybackMode::Once);
+        assert_eq!(once.step(2), Step::Frame(1));
+        assert_eq!(once.step(2), Step::Ended);
+        assert!(once.is_ended());
+        assert_eq!(once.step(0), Step::Hold);
+
+        let mut looping = FrameSequencer::new(PlaybackMode::Loop);
+        looping.step(2);
+        assert_eq!(looping.step(2), Step::Looped(0));
+        assert_eq!(looping.step(1), Step::Hold);
+
+        let mut ping_pong = FrameSequencer::new(PlaybackMode::PingPong);
+        ping_pong.step(2);
+        assert_eq!(ping_pong.step(2), Step::Reversed(0));
+        assert_eq!(ping_pong.direction(), -1);
+        assert_eq!(Step::Reversed(0).event_name(), Some("reversed"));
+    }
+
+    #[test]
+    fn test_peek_does_not_move() {
+        let mut sequencer = FrameSequencer::new(PlaybackMode::PingPong);
+        sequencer.seek(2, 3);
+        assert_eq!(sequencer.peek(3), Some(1));
+        assert_eq!(sequencer.current(), 2);
+        assert_eq!(sequencer.direction(), 1);
+    }
+
+    #[test]
+    fn test_negative_rate_flips_every_mode() {
+        // Loop runs backwards and wraps from the first frame to the last
+        let mut looping = FrameSequencer::new(PlaybackMode::Loop);
+        looping.seek(1, 3);
+        looping.set_reversed(true);
+        assert_eq!([looping.step(3), looping.step(3), looping.step(3)], [Step::Frame(0), Step::Looped(2), Step::Frame(1)]);
+
+        // ReverseLoop turned around plays forwards
+        let mut reverse = FrameSequencer::new(PlaybackMode::ReverseLoop);
+        reverse.set_reversed(true);
+        reverse.rewind(3);
+        assert_eq!([reverse.step(3), reverse.step(3), reverse.step(3)], [Step::Frame(1), Step::Frame(2), Step::Looped(0)]);
+
+        // PingPong still bounces at the ends, just starting the other way
+        let mut ping_pong = FrameSequencer::new(PlaybackMode::PingPong);
+        ping_pong.seek(1, 3);
+        ping_pong.set_reversed(true);
+        assert_eq!(ping_pong.direction(), -1);
+        assert_eq!([ping_pong.step(3), ping_pong.step(3), ping_pong.step(3)], [Step::Frame(0), Step::Reversed(1), Step::Frame(2)]);
+        assert_eq!(ping_pong.direction(), 1);
+
+        // Directions asked for are the ones played, whatever the rate
+        looping.set_direction(1);
+        assert_eq!(looping.direction(), 1);
+        looping.set_mode(PlaybackMode::Once);
+        assert_eq!(looping.direction(), -1);
+    }
+
+    #[test]
+    fn test_changing_mode_clears_end() {
+        let mut sequencer = FrameSequencer::new(PlaybackMode::Once);
+        sequencer.step(1);
+        assert!(sequencer.is_ended());
+
+        sequencer.set_mode(PlaybackMode::Loop);
+        assert!(!sequencer.is_ended());
+        assert_eq!(sequencer.step(3), Step::Frame(1));
+    }
+
+    #[test]
+    fn test_shrinking_sequence_clamps_position() {
+        let mut sequencer = FrameSequencer::new(PlaybackMode::Loop);
+        sequencer.rewind(10);
+        for _ in 0..8 {
+            sequencer.step(10);
+        }
+        assert_eq!(sequencer.step(4), Step::Looped(0));
+    }
+}
@@ -0,0 +1,327 @@
+use serde::{Serialize, Deserialize};
+use super::frame::{Frame, PixelFormat};
+use super::lifecycle::TransitionError;
+
+// Bumped whenever a message changes shape. Both sides reject envelopes from
+// another version instead of guessing at their payloads.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderStatus {
+    Idle = 0,
+    Initializing = 1,
+    Inited = 2,
+    Ready = 3,
+    Open = 4,
+    Pause = 5,
+    Closed = 6,
+    Error = 7,
+}
+
+// Everything posted between the main thread and the decoder worker. On the JS
+// side this is `{ version, requestId?, message: { type, data? } }`; replies
+// carry the `requestId` of the request they answer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerMessage {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u32>,
+    pub message: DecodeMessage,
+}
+
+impl WorkerMessage {
+    pub fn new(message: DecodeMessage) -> Self {
+        Self { version: PROTOCOL_VERSION, request_id: None, message }
+    }
+
+    pub fn reply(request_id: Option<u32>, message: DecodeMessage) -> Self {
+        Self { version: PROTOCOL_VERSION, request_id, message }
+    }
+
+    pub fn check_version(&self) -> Result<(), ErrorDetails> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(ErrorDetails::new(
+                ErrorKind::Protocol,
+                format!("Protocol version {} isn't supported, expected {}", self.version, PROTOCOL_VERSION),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// `type` must come before `data` in JS objects so the payload isn't buffered
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "data")]
+pub enum DecodeMessage {
+    // Main thread -> worker
+    DecoderInit(DecoderOptions),
+    DecoderStart,
+    DecoderPause,
+    DecoderClose,
+    DecoderReset,
+    DecodeVideoBuffer(Vec<TokenPacket>),
+    RequestStats,
+
+    // Worker -> main thread
+    DecoderCreated,
+    WasmLoaded,
+    DecoderInited,
+    DecoderReady,
+    DecoderOpenError(ErrorDetails),
+    DecoderStarted,
+    DecoderPaused,
+    DecoderClosed,
+    DecoderResetDone,
+    DecodedVideoFrame(FramePayload),
+    DecoderStats(StatusSnapshot),
+    DecoderError(ErrorDetails),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecoderOptions {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub settings: DecoderSettings,
+}
+
+impl DecoderOptions {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, settings: DecoderSettings::default() }
+    }
+}
+
+// Applied by the worker to the decoder it creates; unset fields keep the
+// decoder's own defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecoderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_fps: Option<u32>,
+    #[serde(default)]
+    pub adaptive_fps: bool,
+    #[serde(default)]
+    pub debug_mode: bool,
+}
+
+// Same shape `process_tokens` takes. The Player sends `token` as a
+// transferred Float32Array; plain arrays of numbers are accepted too.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenPacket {
+    pub frame_index: usize,
+    pub token: Vec<f32>,
+    #[serde(default)]
+    pub is_keyframe: bool,
+}
+
+// A decoded frame. `data` becomes a Uint8Array whose buffer the worker
+// transfers rather than copies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FramePayload {
+    pub frame_index: usize,
+    pub timestamp: f64,
+    pub width: usize,
+    pub height: usize,
+    pub is_keyframe: bool,
+    #[serde(default)]
+    pub format: PixelFormat,
+    #[serde(with = "bytes")]
+    pub data: Vec<u8>,
+}
+
+impl FramePayload {
+    pub fn new(frame_index: usize, frame: Frame) -> Self {
+        Self {
+            frame_index,
+            timestamp: frame.timestamp,
+            width: frame.width,
+            height: frame.height,
+            is_keyframe: frame.is_keyframe,
+            format: frame.format,
+            data: frame.data,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // The message couldn't be parsed or is from another protocol version
+    Protocol,
+    // The request isn't valid in the worker's current status
+    InvalidState,
+    // Tokens or reference data were rejected by the decoder
+    Decode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorDetails {
+    pub kind: ErrorKind,
+    pub message: String,
+    // Set when a request was rejected by the worker's state machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<TransitionError>,
+}
+
+impl ErrorDetails {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into(), transition: None }
+    }
+}
+
+impl From<TransitionError> for ErrorDetails {
+    fn from(error: TransitionError) -> Self {
+        Self { kind: ErrorKind::InvalidState, message: error.to_string(), transition: Some(error) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSnapshot {
+    pub status: DecoderStatus,
+    pub frames_decoded: u64,
+    pub frames_dropped: usize,
+    pub last_frame_index: Option<usize>,
+    // Input, processing and output queue lengths
+    pub queue_depths: [usize; 3],
+}
+
+// Pixel data goes through `serialize_bytes`, which serde-wasm-bindgen turns
+// into a Uint8Array instead of an array of numbers
+mod bytes {
+    use std::fmt;
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(data)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+
+        // Formats without a bytes type, e.g. JSON, write a sequence
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                data.push(byte);
+            }
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::lifecycle::DecoderEvent;
+
+    fn round_trip(message: &WorkerMessage) -> WorkerMessage {
+        let json = serde_json::to_string(message).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_every_message_round_trips() {
+        let error = ErrorDetails::new(ErrorKind::Decode, "Token length 3 does not match frame size");
+        let mut frame = Frame::new(2, 1);
+        frame.set_data(vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        frame.timestamp = 80.0;
+
+        let messages = [
+            DecodeMessage::DecoderInit(DecoderOptions::new(640, 480)),
+            DecodeMessage::DecoderInit(DecoderOptions {
+                width: 1280,
+                height: 720,
+                settings: DecoderSettings { target_fps: Some(25), adaptive_fps: true, debug_mode: false },
+            }),
+            DecodeMessage::DecoderStart,
+            DecodeMessage::DecoderPause,
+            DecodeMessage::DecoderClose,
+            DecodeMessage::DecoderReset,
+            DecodeMessage::DecodeVideoBuffer(vec![TokenPacket { frame_index: 3, token: vec![0.5; 4], is_keyframe: true }]),
+            DecodeMessage::RequestStats,
+            DecodeMessage::DecoderCreated,
+            DecodeMessage::WasmLoaded,
+            DecodeMessage::DecoderInited,
+            DecodeMessage::DecoderReady,
+            DecodeMessage::DecoderOpenError(error.clone()),
+            DecodeMessage::DecoderStarted,
+            DecodeMessage::DecoderPaused,
+            DecodeMessage::DecoderClosed,
+            DecodeMessage::DecoderResetDone,
+            DecodeMessage::DecodedVideoFrame(FramePayload::new(2, frame)),
+            DecodeMessage::DecoderStats(StatusSnapshot {
+                status: DecoderStatus::Open,
+                frames_decoded: 42,
+                frames_dropped: 1,
+                last_frame_index: Some(41),
+                queue_depths: [3, 0, 7],
+            }),
+            DecodeMessage::DecoderError(error),
+            DecodeMessage::DecoderError(DecoderStatus::Closed.apply(DecoderEvent::Start).unwrap_err().into()),
+        ];
+
+        for (id, message) in messages.into_iter().enumerate() {
+            let envelope = WorkerMessage::reply(Some(id as u32), message);
+            assert_eq!(round_trip(&envelope), envelope);
+        }
+        let unsolicited = WorkerMessage::new(DecodeMessage::DecoderCreated);
+        assert_eq!(round_trip(&unsolicited), unsolicited);
+    }
+
+    #[test]
+    fn test_wire_shape() {
+        let json = serde_json::to_value(WorkerMessage::reply(
+            Some(7),
+            DecodeMessage::DecoderOpenError(ErrorDetails::new(ErrorKind::InvalidState, "not ready")),
+        )).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "requestId": 7,
+            "message": { "type": "DecoderOpenError", "data": { "kind": "InvalidState", "message": "not ready" } },
+        }));
+
+        // Unit messages need no data and replies without a request omit the id
+        let start: WorkerMessage = serde_json::from_str(r#"{"version":4,"message":{"type":"DecoderStart"}}"#).unwrap();
+        assert_eq!(start, WorkerMessage::new(DecodeMessage::DecoderStart));
+        assert!(!serde_json::to_string(&start).unwrap().contains("requestId"));
+
+        // Decoder settings are optional
+        let init: WorkerMessage = serde_json::from_str(
+            r#"{"version":4,"message":{"type":"DecoderInit","data":{"width":64,"height":48}}}"#,
+        ).unwrap();
+        assert_eq!(init.message, DecodeMessage::DecoderInit(DecoderOptions::new(64, 48)));
+    }
+
+    #[test]
+    fn test_version_and_malformed_messages() {
+        let mut message = WorkerMessage::new(DecodeMessage::RequestStats);
+        assert!(message.check_version().is_ok());
+        message.version = PROTOCOL_VERSION + 1;
+        assert_eq!(message.check_version().unwrap_err().kind, ErrorKind::Protocol);
+
+        assert!(serde_json::from_str::<WorkerMessage>(r#"{"version":4,"message":{"type":"Bogus"}}"#).is_err());
+        assert!(serde_json::from_str::<WorkerMessage>(r#"{"message":{"type":"DecoderStart"}}"#).is_err());
+    }
+}
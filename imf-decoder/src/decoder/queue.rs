@@ -1,6 +1,8 @@
 use std::collections::VecDeque;
-// use web_sys::Performance;
+use super::filter::FilterChain;
 use super::frame::Frame;
+use crate::utils::clock::{system_clock, SharedClock};
+use log::warn;
 
 #[derive(Debug, Default)]
 pub struct QueueMetrics {
@@ -18,6 +20,8 @@ pub struct Queue {
     max_size: usize,
     batch_size: usize,
     metrics: QueueMetrics,
+    clock: SharedClock,
+    filters: FilterChain,
 }
 
 #[derive(Debug)]
@@ -36,6 +40,10 @@ pub struct QueueStats {
 
 impl Queue {
     pub fn new(max_size: usize, batch_size: usize) -> Self {
+        Self::with_clock(max_size, batch_size, system_clock())
+    }
+
+    pub fn with_clock(max_size: usize, batch_size: usize, clock: SharedClock) -> Self {
         Self {
             input_queue: VecDeque::with_capacity(max_size),
             processing_queue: VecDeque::with_capacity(batch_size),
@@ -43,6 +51,8 @@ impl Queue {
             max_size,
             batch_size,
             metrics: QueueMetrics::default(),
+            clock,
+            filters: FilterChain::new(),
         }
     }
 
@@ -58,30 +68,38 @@ impl Queue {
     }
 
     pub fn process_next(&mut self) -> Option<Frame> {
-        let start_time = web_sys::window()
-            .and_then(|w| w.performance())
-            .map(|p| p.now())
-            .unwrap_or(0.0);
+        self.run_next(true)
+    }
+
+    // Processes everything waiting and hands it all to the caller. Nothing is
+    // kept in the output stage, which only feeds presentation.
+    pub fn drain_processed(&mut self) -> Vec<Frame> {
+        let mut frames = Vec::with_capacity(self.input_queue.len());
+        while !self.input_queue.is_empty() {
+            frames.extend(self.run_next(false));
+        }
+        frames
+    }
+
+    // `keep` leaves a copy of the frame in the output stage
+    fn run_next(&mut self, keep: bool) -> Option<Frame> {
+        let start_time = self.clock.now();
 
         let result = if let Some(frame) = self.input_queue.pop_front() {
             self.processing_queue.push_back(frame.clone());
-            self.process_frame()
+            self.process_frame(keep)
         } else {
             None
         };
 
         // Record processing time
-        if let Some(window) = web_sys::window() {
-            if let Some(perf) = window.performance() {
-                let processing_time = perf.now() - start_time;
-                self.metrics.processing_times.push(processing_time);
-                self.metrics.last_process_time = processing_time;
-
-                // Keep only last 100 measurements
-                if self.metrics.processing_times.len() > 100 {
-                    self.metrics.processing_times.remove(0);
-                }
-            }
+        let processing_time = self.clock.now() - start_time;
+        self.metrics.processing_times.push(processing_time);
+        self.metrics.last_process_time = processing_time;
+
+        // Keep only last 100 measurements
+        if self.metrics.processing_times.len() > 100 {
+            self.metrics.processing_times.remove(0);
         }
 
         if result.is_some() {
@@ -92,11 +110,32 @@ impl Queue {
         result
     }
 
-    fn process_frame(&mut self) -> Option<Frame> {
-        self.processing_queue.pop_front().map(|frame| {
-            self.output_queue.push_back(frame.clone());
-            frame
-        })
+    // Runs the filter chain; frames it rejects count as dropped
+    fn process_frame(&mut self, keep: bool) -> Option<Frame> {
+        let frame = self.processing_queue.pop_front()?;
+        match self.filters.apply(frame) {
+            Ok(frame) => {
+                if keep {
+                    self.output_queue.push_back(frame.clone());
+                }
+                Some(frame)
+            }
+            Err(e) => {
+                warn!("Dropping frame: {}", e);
+                self.metrics.frames_dropped += 1;
+                None
+            }
+        }
+    }
+
+    // CPU post-processing applied to every processed frame
+    pub fn set_filters(&mut self, filters: FilterChain) {
+        self.filters = filters;
+    }
+
+    // Runs a frame through the filters outside the pipeline (e.g. a seek target)
+    pub fn filter_frame(&mut self, frame: Frame) -> Result<Frame, String> {
+        self.filters.apply(frame)
     }
 
     pub fn process_batch(&mut self) -> Vec<Frame> {
@@ -158,6 +197,11 @@ impl Queue {
         self.metrics.last_process_time
     }
 
+    // Processed frames waiting to be presented, oldest first
+    pub fn output_mut(&mut self) -> &mut VecDeque<Frame> {
+        &mut self.output_queue
+    }
+
     // Utility Methods
     fn get_average_processing_time(&self) -> f64 {
         if self.metrics.processing_times.is_empty() {
@@ -188,10 +232,20 @@ impl Queue {
         self.max_size - self.input_queue.len()
     }
 
+    // Drops pending frames in every stage but keeps the counters
+    pub fn flush(&mut self) {
+        self.input_queue.clear();
+        self.processing_queue.clear();
+        self.output_queue.clear();
+        self.filters.reset();
+        self.update_metrics();
+    }
+
     pub fn clear(&mut self) {
         self.input_queue.clear();
         self.processing_queue.clear();
         self.output_queue.clear();
+        self.filters.reset();
         self.metrics = QueueMetrics::default();
     }
 }
@@ -205,6 +259,9 @@ impl Drop for Queue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::decoder::filter::FrameFilter;
+    use crate::utils::clock::VirtualClock;
+    use std::rc::Rc;
 
     #[test]
     fn test_queue_capacity() {
@@ -253,6 +310,103 @@ mod tests {
         assert_eq!(queue.get_frames_dropped(), 1);
     }
 
+    // Takes `cost` ms of virtual time per frame
+    struct SlowFilter {
+        clock: VirtualClock,
+        cost: f64,
+    }
+
+    impl FrameFilter for SlowFilter {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn apply(&mut self, frame: Frame) -> Result<Frame, String> {
+            self.clock.advance(self.cost);
+            Ok(frame)
+        }
+    }
+
+    #[test]
+    fn test_processing_time_uses_injected_clock() {
+        let clock = VirtualClock::new(1000.0);
+        let mut queue = Queue::with_clock(4, 2, Rc::new(clock.clone()));
+        let mut filters = FilterChain::new();
+        filters.push(Box::new(SlowFilter { clock: clock.clone(), cost: 12.5 }));
+        queue.set_filters(filters);
+
+        // Time spent waiting in the input queue isn't processing time
+        queue.push(Frame::new(16, 16));
+        clock.advance(50.0);
+        assert!(queue.process_next().is_some());
+        assert_eq!(queue.get_metrics().last_process_time, 12.5);
+
+        queue.set_filters(FilterChain::new());
+        queue.push(Frame::new(16, 16));
+        assert!(queue.process_next().is_some());
+
+        let stats = queue.get_metrics();
+        assert_eq!(stats.last_process_time, 0.0);
+        assert_eq!(stats.average_processing_time, 6.25);
+    }
+
+    #[test]
+    fn test_processed_frames_reach_output() {
+        let mut queue = Queue::new(10, 4);
+        for i in 0..3 {
+            let mut frame = Frame::new(2, 2);
+            frame.timestamp = i as f64 * 40.0;
+            queue.push(frame);
+        }
+
+        queue.process_batch();
+        let output = queue.output_mut();
+        let timestamps: Vec<f64> = output.iter().map(|frame| frame.timestamp).collect();
+        assert_eq!(timestamps, vec![0.0, 40.0, 80.0]);
+
+        output.pop_front();
+        assert_eq!(queue.get_queue_sizes(), (0, 0, 2));
+    }
+
+    #[test]
+    fn test_drain_hands_over_every_frame() {
+        let mut queue = Queue::new(10, 4);
+        for i in 0..6 {
+            let mut frame = Frame::new(2, 2);
+            frame.frame_index = i;
+            queue.push(frame);
+        }
+
+        // Past the batch size, and nothing left behind for presentation
+        let indices: Vec<usize> = queue.drain_processed().iter().map(|frame| frame.frame_index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(queue.get_queue_sizes(), (0, 0, 0));
+        assert_eq!(queue.get_frames_processed(), 6);
+    }
+
+    #[test]
+    fn test_filters_run_in_processing_stage() {
+        use crate::decoder::filter::{FilterChain, FilterConfig};
+
+        let mut queue = Queue::new(4, 4);
+        let configs = [FilterConfig::Levels { black: 0, white: 127, gamma: 1.0 }];
+        queue.set_filters(FilterChain::from_configs(&configs).unwrap());
+
+        let mut frame = Frame::new(1, 1);
+        frame.set_data(vec![100, 50, 127, 255]);
+        queue.push(frame);
+
+        let mut broken = Frame::new(1, 1);
+        broken.data.clear();
+        queue.push(broken);
+
+        let processed = queue.process_batch();
+        assert_eq!(processed.len(), 1);
+        assert_eq!(processed[0].data, vec![201, 100, 255, 255]);
+        assert_eq!(queue.output_mut().len(), 1);
+        assert_eq!(queue.get_frames_dropped(), 1);
+    }
+
     #[test]
     fn test_queue_metrics() {
         let mut queue = Queue::new(5, 2);
@@ -0,0 +1,409 @@
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use super::frame::Frame;
+use crate::utils::clock::SharedClock;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PresentationStats {
+    pub presented: u64,
+    pub dropped: u64,
+    pub repeated: u64,
+    // Frames passed over by a seek; not late, so not counted as dropped
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Presentation<T> {
+    // A new frame is due and should be drawn
+    Present(T),
+    // The next frame is overdue but not available, keep showing the last one
+    Repeat,
+    // The current frame is still inside its display window
+    Hold,
+    // Nothing has been presented yet and nothing is due
+    Waiting,
+}
+
+impl<T> Presentation<T> {
+    pub fn is_present(&self) -> bool {
+        matches!(self, Presentation::Present(_))
+    }
+}
+
+// Maps the playback clock to frame presentation times (PTS, in ms from the
+// start of the stream) and decides what should be on screen right now. The
+// media clock runs at `rate` times wall-clock speed; negative rates run it
+// backwards.
+pub struct PresentationScheduler {
+    clock: SharedClock,
+    frame_interval: f64,
+    rate: f64,
+    // (wall clock time, media time) at the last start/resume
+    anchor: Option<(f64, f64)>,
+    paused_position: f64,
+    last_presented: Option<f64>,
+    last_repeat_slot: Option<u64>,
+    // Frames up to this PTS were jumped over by the last seek
+    seek_point: Option<f64>,
+    stats: PresentationStats,
+}
+
+impl PresentationScheduler {
+    pub fn new(clock: SharedClock, frame_interval: f64) -> Self {
+        Self {
+            clock,
+            frame_interval,
+            rate: 1.0,
+            anchor: None,
+            paused_position: 0.0,
+            last_presented: None,
+            last_repeat_slot: None,
+            seek_point: None,
+            stats: PresentationStats::default(),
+        }
+    }
+
+    pub fn set_frame_interval(&mut self, frame_interval: f64) {
+        self.frame_interval = frame_interval;
+    }
+
+    pub fn frame_interval(&self) -> f64 {
+        self.frame_interval
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    // Changes speed without jumping: the media clock continues from where it is
+    pub fn set_rate(&mut self, rate: f64) {
+        if self.anchor.is_some() {
+            self.anchor = Some((self.clock.now(), self.media_time()));
+        }
+        self.rate = rate;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.anchor.is_some()
+    }
+
+    // Starts or resumes the playback clock from the current media position
+    pub fn start(&mut self) {
+        if self.anchor.is_none() {
+            self.anchor = Some((self.clock.now(), self.paused_position));
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused_position = self.media_time();
+        self.anchor = None;
+    }
+
+    pub fn reset(&mut self) {
+        self.anchor = self.anchor.map(|_| (self.clock.now(), 0.0));
+        self.paused_position = 0.0;
+        self.last_presented = None;
+        self.last_repeat_slot = None;
+        self.seek_point = None;
+        self.stats = PresentationStats::default();
+    }
+
+    // Jumps the media clock to `pts` and treats the frame there as on screen
+    pub fn seek(&mut self, pts: f64) {
+        match self.anchor {
+            Some(_) => self.anchor = Some((self.clock.now(), pts)),
+            None => self.paused_position = pts,
+        }
+        self.last_presented = Some(pts);
+        self.last_repeat_slot = None;
+        self.seek_point = Some(pts);
+    }
+
+    // Current position on the media timeline in ms
+    pub fn media_time(&self) -> f64 {
+        match self.anchor {
+            Some((wall, media)) => media + (self.clock.now() - wall) * self.rate,
+            None => self.paused_position,
+        }
+    }
+
+    // Slot of the frame on screen, None before the first presentation
+    pub fn presented_slot(&self) -> Option<i64> {
+        self.last_presented.map(|pts| self.slot(pts))
+    }
+
+    pub fn stats(&self) -> PresentationStats {
+        self.stats
+    }
+
+    // Wall-clock ms until the next frame becomes due, 0 if one is overdue
+    pub fn time_until_next_deadline(&self) -> f64 {
+        let Some(last) = self.last_presented else {
+            return 0.0;
+        };
+        if self.rate == 0.0 {
+            return f64::INFINITY;
+        }
+
+        // Media ms left before leaving the current frame's slot
+        let remaining = if self.rate > 0.0 {
+            last + self.frame_interval - self.media_time()
+        } else {
+            self.media_time() - last
+        };
+        (remaining / self.rate.abs()).max(0.0)
+    }
+
+    // How far through the current frame's slot the media clock is, 0..1
+    pub fn phase(&self) -> f64 {
+        if self.frame_interval <= 0.0 {
+            return 0.0;
+        }
+        let position = self.media_time() / self.frame_interval;
+        position - position.floor()
+    }
+
+    // Picks the newest due frame from a PTS-ordered queue. Older due frames and
+    // frames behind the one already on screen are dropped as late; frames
+    // before a seek point are skipped.
+    pub fn select_frame(&mut self, pending: &mut VecDeque<Frame>) -> Presentation<Frame> {
+        let now = self.media_time();
+        let mut due: Option<Frame> = None;
+
+        while pending.front().is_some_and(|frame| frame.timestamp <= now) {
+            let Some(frame) = pending.pop_front() else { break };
+            if self.seek_point.is_some_and(|pts| frame.timestamp <= pts) {
+                self.stats.skipped += 1;
+                continue;
+            }
+            let stale = self.last_presented.is_some_and(|last| frame.timestamp <= last);
+            if stale || due.replace(frame).is_some() {
+                self.stats.dropped += 1;
+            }
+        }
+
+        match due {
+            Some(frame) => {
+                self.mark_presented(frame.timestamp);
+                Presentation::Present(frame)
+            }
+            None => self.idle(now),
+        }
+    }
+
+    // Slot-based variant for sources where every frame is always available
+    // (preloaded bitmaps, generated patterns). Returns how many frame periods
+    // passed since the last presented frame in either direction; all but the
+    // last were dropped. The first presentation reports the slot it landed on.
+    pub fn advance(&mut self) -> Presentation<u64> {
+        let now = self.media_time();
+        let slot = self.slot(now);
+        let steps = match self.last_presented {
+            Some(last) => {
+                let last_slot = self.slot(last);
+                if slot == last_slot {
+                    return Presentation::Hold;
+                }
+                slot.abs_diff(last_slot)
+            }
+            None => slot.unsigned_abs(),
+        };
+
+        self.stats.dropped += steps.saturating_sub(1);
+        self.mark_presented(slot as f64 * self.frame_interval);
+        Presentation::Present(steps)
+    }
+
+    fn mark_presented(&mut self, pts: f64) {
+        self.last_presented = Some(pts);
+        self.last_repeat_slot = None;
+        self.stats.presented += 1;
+    }
+
+    fn idle<T>(&mut self, now: f64) -> Presentation<T> {
+        let Some(last) = self.last_presented else {
+            return Presentation::Waiting;
+        };
+
+        if now < last + self.frame_interval {
+            return Presentation::Hold;
+        }
+
+        // Count each missed slot once, however many times we get polled in it
+        let slot = self.slot(now).max(0) as u64;
+        if self.last_repeat_slot != Some(slot) {
+            self.last_repeat_slot = Some(slot);
+            self.stats.repeated += 1;
+        }
+        Presentation::Repeat
+    }
+
+    fn slot(&self, media_time: f64) -> i64 {
+        if self.frame_interval > 0.0 {
+            (media_time / self.frame_interval).floor() as i64
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::VirtualClock;
+    use std::rc::Rc;
+
+    fn frame_at(pts: f64) -> Frame {
+        let mut frame = Frame::new(2, 2);
+        frame.timestamp = pts;
+        frame
+    }
+
+    fn scheduler(clock: &VirtualClock) -> PresentationScheduler {
+        let mut scheduler = PresentationScheduler::new(Rc::new(clock.clone()), 40.0);
+        scheduler.start();
+        scheduler
+    }
+
+    #[test]
+    fn test_presents_newest_due_frame_and_drops_late_ones() {
+        let clock = VirtualClock::new(500.0);
+        let mut scheduler = scheduler(&clock);
+        let mut pending: VecDeque<Frame> = (0..5).map(|i| frame_at(i as f64 * 40.0)).collect();
+
+        clock.advance(90.0);
+        let Presentation::Present(frame) = scheduler.select_frame(&mut pending) else {
+            panic!("expected a frame to be due");
+        };
+        assert_eq!(frame.timestamp, 80.0);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.presented, 1);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(pending.len(), 2);
+    }
+
+    #[test]
+    fn test_holds_then_repeats_when_decoder_falls_behind() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+        let mut pending = VecDeque::from(vec![frame_at(0.0)]);
+
+        assert!(scheduler.select_frame(&mut pending).is_present());
+
+        clock.advance(20.0);
+        assert!(matches!(scheduler.select_frame(&mut pending), Presentation::Hold));
+
+        // Frame at 40ms never arrives; polling twice in one slot counts once
+        clock.advance(25.0);
+        assert!(matches!(scheduler.select_frame(&mut pending), Presentation::Repeat));
+        clock.advance(5.0);
+        assert!(matches!(scheduler.select_frame(&mut pending), Presentation::Repeat));
+        clock.advance(40.0);
+        assert!(matches!(scheduler.select_frame(&mut pending), Presentation::Repeat));
+
+        assert_eq!(scheduler.stats().repeated, 2);
+    }
+
+    #[test]
+    fn test_advance_counts_skipped_slots_as_dropped() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+
+        assert_eq!(scheduler.advance(), Presentation::Present(0));
+        clock.advance(10.0);
+        assert_eq!(scheduler.advance(), Presentation::Hold);
+        clock.advance(30.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(1));
+        clock.advance(130.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(3));
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.presented, 3);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.repeated, 0);
+    }
+
+    #[test]
+    fn test_seek_moves_clock_and_skips_earlier_frames() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+        let mut pending: VecDeque<Frame> = (0..10).map(|i| frame_at(i as f64 * 40.0)).collect();
+
+        scheduler.seek(200.0);
+        assert_eq!(scheduler.media_time(), 200.0);
+        assert!(matches!(scheduler.select_frame(&mut pending), Presentation::Hold));
+
+        clock.advance(40.0);
+        let Presentation::Present(frame) = scheduler.select_frame(&mut pending) else {
+            panic!("expected the frame after the seek point");
+        };
+        assert_eq!(frame.timestamp, 240.0);
+        assert_eq!(scheduler.stats().skipped, 6);
+        assert_eq!(scheduler.stats().dropped, 0);
+
+        // Falling behind after the seek still counts as dropping
+        clock.advance(100.0);
+        assert!(scheduler.select_frame(&mut pending).is_present());
+        assert_eq!(scheduler.stats().dropped, 1);
+    }
+
+    #[test]
+    fn test_rate_scales_media_clock() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+
+        scheduler.set_rate(0.25);
+        assert_eq!(scheduler.advance(), Presentation::Present(0));
+        assert_eq!(scheduler.time_until_next_deadline(), 160.0);
+
+        clock.advance(80.0);
+        assert_eq!(scheduler.advance(), Presentation::Hold);
+        assert_eq!(scheduler.phase(), 0.5);
+
+        clock.advance(80.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(1));
+
+        // Speeding up continues from the current position
+        scheduler.set_rate(4.0);
+        clock.advance(20.0);
+        assert_eq!(scheduler.media_time(), 120.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(2));
+    }
+
+    #[test]
+    fn test_negative_rate_runs_backwards() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+
+        scheduler.seek(400.0);
+        scheduler.set_rate(-2.0);
+        assert_eq!(scheduler.time_until_next_deadline(), 0.0);
+
+        clock.advance(10.0);
+        assert_eq!(scheduler.media_time(), 380.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(1));
+        assert_eq!(scheduler.time_until_next_deadline(), 10.0);
+
+        clock.advance(55.0);
+        assert_eq!(scheduler.media_time(), 270.0);
+        assert_eq!(scheduler.advance(), Presentation::Present(3));
+        assert_eq!(scheduler.stats().dropped, 2);
+    }
+
+    #[test]
+    fn test_pause_freezes_media_time() {
+        let clock = VirtualClock::new(0.0);
+        let mut scheduler = scheduler(&clock);
+
+        clock.advance(100.0);
+        scheduler.pause();
+        clock.advance(1000.0);
+        assert_eq!(scheduler.media_time(), 100.0);
+
+        scheduler.start();
+        clock.advance(20.0);
+        assert_eq!(scheduler.media_time(), 120.0);
+    }
+}
@@ -0,0 +1,333 @@
+use wasm_bindgen::prelude::*;
+use serde::{Serialize, Deserialize};
+use super::frame::Frame;
+
+// Synthetic sources for exercising the pipeline without a model
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestSignal {
+    // Animated sine gradient, the original debug pattern
+    #[default]
+    Sine = 0,
+    // SMPTE 75% bars with the reverse-bar and PLUGE rows
+    ColorBars = 1,
+    // White square bouncing over dark gray
+    MovingBox = 2,
+    // Circular zone plate reaching Nyquist at the edges, phase animated
+    ZonePlate = 3,
+    Checkerboard = 4,
+    // Smooth ramp on top, 16 steps below
+    GrayRamp = 5,
+}
+
+// Generates frame `index` of `signal` as an opaque RGBA frame
+pub fn generate_signal(signal: TestSignal, width: usize, height: usize, index: u64) -> Frame {
+    let mut frame = Frame::new(width, height);
+    let t = index as f64;
+
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = match signal {
+                TestSignal::Sine => sine(x, y, t),
+                TestSignal::ColorBars => color_bars(x, y, width, height),
+                TestSignal::MovingBox => moving_box(x, y, width, height, index),
+                TestSignal::ZonePlate => zone_plate(x, y, width, height, t),
+                TestSignal::Checkerboard => checkerboard(x, y, width, height),
+                TestSignal::GrayRamp => gray_ramp(x, y, width, height),
+            };
+
+            let idx = (y * width + x) * 4;
+            frame.data[idx..idx + 4].copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+    frame
+}
+
+fn sine(x: usize, y: usize, t: f64) -> [u8; 3] {
+    let time = t * 0.05;
+    let r = (((x as f64 * 0.01 + time).sin() + 1.0) * 127.5) as u8;
+    let g = (((y as f64 * 0.01 + time).cos() + 1.0) * 127.5) as u8;
+    let b = ((((x + y) as f64 * 0.01 + time).sin() + 1.0) * 127.5) as u8;
+    [r, g, b]
+}
+
+fn color_bars(x: usize, y: usize, width: usize, height: usize) -> [u8; 3] {
+    const BARS: [[u8; 3]; 7] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+    ];
+    const REVERSE: [[u8; 3]; 7] = [
+        [0, 0, 191],
+        [19, 19, 19],
+        [191, 0, 191],
+        [19, 19, 19],
+        [0, 191, 191],
+        [19, 19, 19],
+        [191, 191, 191],
+    ];
+
+    // Position in bar widths, 0..7
+    let bar = x as f64 * 7.0 / width as f64;
+    if y * 3 < height * 2 {
+        BARS[bar as usize]
+    } else if y * 4 < height * 3 {
+        REVERSE[bar as usize]
+    } else {
+        // -I, white, +Q and black at 5/4 bar each, then PLUGE and black
+        match bar {
+            b if b < 1.25 => [0, 33, 76],
+            b if b < 2.5 => [255, 255, 255],
+            b if b < 3.75 => [50, 0, 106],
+            b if b < 5.0 => [19, 19, 19],
+            b if b < 5.0 + 1.0 / 3.0 => [9, 9, 9],
+            b if b < 5.0 + 2.0 / 3.0 => [19, 19, 19],
+            b if b < 6.0 => [29, 29, 29],
+            _ => [19, 19, 19],
+        }
+    }
+}
+
+fn moving_box(x: usize, y: usize, width: usize, height: usize, index: u64) -> [u8; 3] {
+    let size = (width.min(height) / 8).max(1);
+    let speed = (width / 100).max(1) as u64;
+    let box_x = bounce(index * speed, width.saturating_sub(size));
+    let box_y = bounce(index * speed / 2, height.saturating_sub(size));
+
+    if (box_x..box_x + size).contains(&x) && (box_y..box_y + size).contains(&y) {
+        [255, 255, 255]
+    } else {
+        [32, 32, 32]
+    }
+}
+
+// Triangle wave over 0..=range
+fn bounce(position: u64, range: usize) -> usize {
+    if range == 0 {
+        return 0;
+    }
+    let period = 2 * range as u64;
+    let phase = (position % period) as usize;
+    if phase <= range { phase } else { 2 * range - phase }
+}
+
+fn zone_plate(x: usize, y: usize, width: usize, height: usize, t: f64) -> [u8; 3] {
+    let dx = x as f64 - width as f64 / 2.0;
+    let dy = y as f64 - height as f64 / 2.0;
+    let k = std::f64::consts::PI / width.max(height) as f64;
+    let value = ((k * (dx * dx + dy * dy) + t * 0.2).cos() + 1.0) * 127.5;
+    let value = value.round() as u8;
+    [value; 3]
+}
+
+fn checkerboard(x: usize, y: usize, width: usize, height: usize) -> [u8; 3] {
+    let size = (width.min(height) / 8).max(1);
+    if (x / size + y / size).is_multiple_of(2) { [255; 3] } else { [0; 3] }
+}
+
+fn gray_ramp(x: usize, y: usize, width: usize, height: usize) -> [u8; 3] {
+    let span = width.saturating_sub(1).max(1);
+    let value = if y * 2 < height {
+        (x * 255 / span) as u8
+    } else {
+        let step = (x * 16 / width).min(15);
+        (step * 255 / 15) as u8
+    };
+    [value; 3]
+}
+
+// Frame counter and timestamp carried by a frame's barcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameStamp {
+    pub frame_index: u32,
+    pub timestamp_ms: u32,
+}
+
+// Barcode layout across the top of the frame: a white/black sync pair, then
+// the frame index and timestamp (32 bits each, MSB first) and a CRC-8, one
+// black or white cell per bit
+const SYNC_CELLS: usize = 2;
+const PAYLOAD_BITS: usize = 64;
+const CRC_BITS: usize = 8;
+const BARCODE_CELLS: usize = SYNC_CELLS + PAYLOAD_BITS + CRC_BITS;
+
+impl FrameStamp {
+    fn bytes(&self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&self.frame_index.to_be_bytes());
+        bytes[4..].copy_from_slice(&self.timestamp_ms.to_be_bytes());
+        bytes
+    }
+
+    fn cells(&self) -> [bool; BARCODE_CELLS] {
+        let bytes = self.bytes();
+        let crc = crc8(&bytes);
+
+        let mut cells = [false; BARCODE_CELLS];
+        cells[0] = true;
+        for (i, cell) in cells[SYNC_CELLS..].iter_mut().enumerate() {
+            let byte = if i < PAYLOAD_BITS { bytes[i / 8] } else { crc };
+            *cell = byte & (0x80 >> (i % 8)) != 0;
+        }
+        cells
+    }
+}
+
+// Barcode strip height for a frame
+fn barcode_rows(height: usize) -> usize {
+    (height / 16).max(2)
+}
+
+// Draws the barcode over the top rows, converting the frame to RGBA.
+// Returns false, leaving the frame untouched, when it is too small to hold
+// one cell per bit.
+pub fn embed_barcode(frame: &mut Frame, stamp: FrameStamp) -> bool {
+    let cell_width = frame.width / BARCODE_CELLS;
+    let rows = barcode_rows(frame.height);
+    if cell_width == 0 || rows > frame.height {
+        return false;
+    }
+    let Ok(mut rgba) = frame.to_rgba() else {
+        return false;
+    };
+
+    let cells = stamp.cells();
+    for y in 0..rows {
+        for x in 0..frame.width {
+            let on = cells.get(x / cell_width).copied().unwrap_or(false);
+            let value = if on { 255 } else { 0 };
+            let idx = (y * frame.width + x) * 4;
+            rgba.data[idx..idx + 4].copy_from_slice(&[value, value, value, 255]);
+        }
+    }
+
+    *frame = rgba;
+    true
+}
+
+// Reads a barcode written by embed_barcode, sampling each cell at its centre.
+// None when there is no sync pair or the CRC doesn't match.
+pub fn read_barcode(frame: &Frame) -> Option<FrameStamp> {
+    let cell_width = frame.width / BARCODE_CELLS;
+    let rows = barcode_rows(frame.height);
+    if cell_width == 0 || rows > frame.height {
+        return None;
+    }
+    let rgba = frame.as_rgba().ok()?;
+
+    let y = rows / 2;
+    let cell = |i: usize| {
+        let x = i * cell_width + cell_width / 2;
+        let idx = (y * rgba.width + x) * 4;
+        let luma = rgba.data[idx..idx + 3].iter().map(|&v| v as u32).sum::<u32>() / 3;
+        luma >= 128
+    };
+
+    if !cell(0) || cell(1) {
+        return None;
+    }
+
+    let mut bytes = [0u8; 9];
+    for i in 0..PAYLOAD_BITS + CRC_BITS {
+        if cell(SYNC_CELLS + i) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    if crc8(&bytes[..8]) != bytes[8] {
+        return None;
+    }
+
+    Some(FrameStamp {
+        frame_index: u32::from_be_bytes(bytes[..4].try_into().unwrap()),
+        timestamp_ms: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+    })
+}
+
+// CRC-8, polynomial 0x07
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(frame: &Frame, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * frame.width + x) * 4;
+        frame.data[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_signals_at_any_resolution() {
+        let signals = [
+            TestSignal::Sine,
+            TestSignal::ColorBars,
+            TestSignal::MovingBox,
+            TestSignal::ZonePlate,
+            TestSignal::Checkerboard,
+            TestSignal::GrayRamp,
+        ];
+        for signal in signals {
+            for (width, height) in [(1, 1), (7, 3), (64, 36)] {
+                let frame = generate_signal(signal, width, height, 5);
+                assert!(frame.is_valid());
+                assert!(frame.data.chunks(4).all(|pixel| pixel[3] == 255), "{:?}", signal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_color_bars_layout() {
+        let bars = generate_signal(TestSignal::ColorBars, 70, 12, 0);
+        assert_eq!(pixel(&bars, 0, 0), [191, 191, 191, 255]);
+        assert_eq!(pixel(&bars, 15, 0), [191, 191, 0, 255]);
+        assert_eq!(pixel(&bars, 69, 7), [0, 0, 191, 255]);
+        assert_eq!(pixel(&bars, 0, 8), [0, 0, 191, 255]);
+        assert_eq!(pixel(&bars, 20, 11), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_ramp_and_moving_box() {
+        let ramp = generate_signal(TestSignal::GrayRamp, 256, 4, 0);
+        assert_eq!(pixel(&ramp, 0, 0)[0], 0);
+        assert_eq!(pixel(&ramp, 255, 0)[0], 255);
+        assert_eq!(pixel(&ramp, 20, 3)[0], 17);
+
+        let first = generate_signal(TestSignal::MovingBox, 200, 100, 0);
+        let later = generate_signal(TestSignal::MovingBox, 200, 100, 10);
+        assert_eq!(pixel(&first, 0, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&later, 0, 0), [32, 32, 32, 255]);
+        assert_eq!(pixel(&later, 25, 15), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_barcode_round_trip() {
+        let stamp = FrameStamp { frame_index: 123_456, timestamp_ms: 4_115_033 };
+        for signal in [TestSignal::Sine, TestSignal::Checkerboard, TestSignal::ColorBars] {
+            let mut frame = generate_signal(signal, 320, 180, 7);
+            assert!(embed_barcode(&mut frame, stamp));
+            assert_eq!(read_barcode(&frame), Some(stamp));
+        }
+
+        // Unmarked, corrupted and too-small frames don't decode
+        let mut frame = generate_signal(TestSignal::GrayRamp, 320, 180, 0);
+        assert_eq!(read_barcode(&frame), None);
+        embed_barcode(&mut frame, stamp);
+        let cell = 320 / BARCODE_CELLS;
+        for y in 0..barcode_rows(180) {
+            let idx = (y * 320 + 5 * cell + cell / 2) * 4;
+            frame.data[idx..idx + 3].iter_mut().for_each(|v| *v = 255 - *v);
+        }
+        assert_eq!(read_barcode(&frame), None);
+        assert!(!embed_barcode(&mut Frame::new(40, 40), stamp));
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use super::frame::Frame;
+
+struct StoredToken {
+    token: Vec<f32>,
+    is_keyframe: bool,
+}
+
+impl StoredToken {
+    fn bytes(&self) -> usize {
+        self.token.len() * std::mem::size_of::<f32>()
+    }
+}
+
+// Token memory kept for seeking back before the oldest tokens are evicted
+pub const DEFAULT_RETENTION_BYTES: usize = 256 << 20;
+
+// Every token received so far, indexed by frame, so decoded streams can be
+// re-decoded when seeking. Tokens decode independently of each other;
+// keyframes only mark where a stream can be joined. Once the tokens pass
+// `max_bytes` the oldest are dropped, a keyframe interval at a time.
+pub struct TokenStream {
+    tokens: BTreeMap<usize, StoredToken>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Default for TokenStream {
+    fn default() -> Self {
+        Self::with_retention(DEFAULT_RETENTION_BYTES)
+    }
+}
+
+impl TokenStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_retention(max_bytes: usize) -> Self {
+        Self { tokens: BTreeMap::new(), bytes: 0, max_bytes }
+    }
+
+    // The first frame of a stream is always treated as a keyframe
+    pub fn insert(&mut self, frame_index: usize, token: Vec<f32>, is_keyframe: bool) {
+        let is_keyframe = is_keyframe || frame_index == 0;
+        let stored = StoredToken { token, is_keyframe };
+        self.bytes += stored.bytes();
+        if let Some(replaced) = self.tokens.insert(frame_index, stored) {
+            self.bytes -= replaced.bytes();
+        }
+        self.evict();
+    }
+
+    // Drops everything before the second-oldest keyframe until the stream fits,
+    // so what remains still starts at a keyframe. Without a later keyframe the
+    // oldest tokens go one by one. The newest token is always kept.
+    fn evict(&mut self) {
+        while self.bytes > self.max_bytes && self.tokens.len() > 1 {
+            let next_keyframe = self.tokens
+                .iter()
+                .skip(1)
+                .find(|(_, stored)| stored.is_keyframe)
+                .map(|(&index, _)| index);
+
+            let evicted = match next_keyframe {
+                Some(keyframe) => {
+                    let kept = self.tokens.split_off(&keyframe);
+                    std::mem::replace(&mut self.tokens, kept)
+                }
+                None => self.tokens.pop_first().into_iter().collect(),
+            };
+            self.bytes -= evicted.values().map(StoredToken::bytes).sum::<usize>();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    // Number of frame slots, including gaps, from frame 0 to the last token
+    pub fn frame_count(&self) -> usize {
+        self.tokens.keys().next_back().map_or(0, |last| last + 1)
+    }
+
+    // Size of the token stored for `frame_index`, as sent (f32 values)
+    pub fn packet_bits(&self, frame_index: usize) -> Option<usize> {
+        self.tokens.get(&frame_index).map(|stored| stored.token.len() * 32)
+    }
+
+    // Oldest frame still stored
+    pub fn first_frame(&self) -> Option<usize> {
+        self.tokens.keys().next().copied()
+    }
+
+    // Memory held by stored tokens
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+        self.bytes = 0;
+    }
+
+    // Nearest keyframe at or before `frame_index`, falling back to the first token
+    pub fn keyframe_before(&self, frame_index: usize) -> Option<usize> {
+        self.tokens
+            .range(..=frame_index)
+            .rev()
+            .find(|(_, stored)| stored.is_keyframe)
+            .map(|(index, _)| *index)
+            .or_else(|| self.tokens.keys().next().copied())
+    }
+
+    // Decodes the stored tokens for frames `first..=last`
+    pub fn decode_range(
+        &self,
+        first: usize,
+        last: usize,
+        width: usize,
+        height: usize,
+        frame_interval: f64,
+    ) -> Result<Vec<Frame>, String> {
+        self.tokens
+            .range(first..=last.max(first))
+            .map(|(&index, stored)| Self::decode_stored(index, stored, width, height, frame_interval))
+            .collect()
+    }
+
+    // Decodes the token for `frame_index`, None if it hasn't arrived
+    pub fn decode_frame(
+        &self,
+        frame_index: usize,
+        width: usize,
+        height: usize,
+        frame_interval: f64,
+    ) -> Option<Result<Frame, String>> {
+        self.tokens
+            .get(&frame_index)
+            .map(|stored| Self::decode_stored(frame_index, stored, width, height, frame_interval))
+    }
+
+    fn decode_stored(
+        index: usize,
+        stored: &StoredToken,
+        width: usize,
+        height: usize,
+        frame_interval: f64,
+    ) -> Result<Frame, String> {
+        let mut frame = decode_token(&stored.token, width, height)?;
+        frame.frame_index = index;
+        frame.timestamp = index as f64 * frame_interval;
+        frame.is_keyframe = stored.is_keyframe;
+        Ok(frame)
+    }
+}
+
+// Turns one frame token into RGBA pixels
+pub fn decode_token(token: &[f32], width: usize, height: usize) -> Result<Frame, String> {
+    let mut frame = Frame::new(width, height);
+    if token.len() != frame.data.len() {
+        return Err(format!(
+            "Token length {} does not match frame size {}x{} ({} bytes)",
+            token.len(), width, height, frame.data.len()
+        ));
+    }
+
+    frame.set_data(token.iter().map(|&x| x as u8).collect());
+    Ok(frame)
+}
+
+// Uniform quantization of a token to `bits` per value between its own min and
+// max, returning the values the receiver would reconstruct. The range is sent
+// alongside as two f32s, see `quantized_token_bits`.
+pub fn quantize_token(token: &[f32], bits: u8) -> Result<Vec<f32>, String> {
+    if !(1..=16).contains(&bits) {
+        return Err(format!("Quantization needs 1-16 bits per value, got {}", bits));
+    }
+
+    let (min, max) = token.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+    if token.is_empty() || max <= min {
+        return Ok(token.to_vec());
+    }
+
+    let steps = ((1u32 << bits) - 1) as f32;
+    let scale = (max - min) / steps;
+    Ok(token.iter().map(|&x| min + ((x - min) / scale).round() * scale).collect())
+}
+
+// Size on the wire of a token of `len` values quantized to `bits`
+pub fn quantized_token_bits(len: usize, bits: u8) -> usize {
+    len * bits as usize + 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(value: f32) -> Vec<f32> {
+        vec![value; 2 * 2 * 4]
+    }
+
+    #[test]
+    fn test_keyframe_lookup() {
+        let mut stream = TokenStream::new();
+        for i in 0..10 {
+            stream.insert(i, token(i as f32), i % 4 == 0);
+        }
+
+        assert_eq!(stream.keyframe_before(0), Some(0));
+        assert_eq!(stream.keyframe_before(3), Some(0));
+        assert_eq!(stream.keyframe_before(4), Some(4));
+        assert_eq!(stream.keyframe_before(9), Some(8));
+        assert_eq!(stream.frame_count(), 10);
+    }
+
+    #[test]
+    fn test_decode_range_and_single_frames() {
+        let mut stream = TokenStream::new();
+        for i in (0..6).filter(|&i| i != 2) {
+            stream.insert(i, token(i as f32 * 10.0), i == 3);
+        }
+
+        let frames = stream.decode_range(4, 5, 2, 2, 40.0).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].frame_index, frames[0].timestamp), (4, 160.0));
+        assert_eq!(frames[0].data[0], 40);
+        assert_eq!(frames[1].data[0], 50);
+
+        let frame = stream.decode_frame(3, 2, 2, 40.0).unwrap().unwrap();
+        assert!(frame.is_keyframe);
+        assert_eq!(frame.data[0], 30);
+        // Gaps decode to nothing rather than a neighbour
+        assert!(stream.decode_frame(2, 2, 2, 40.0).is_none());
+    }
+
+    #[test]
+    fn test_retention_evicts_whole_keyframe_intervals() {
+        // Room for 6 tokens of 64 bytes
+        let mut stream = TokenStream::with_retention(6 * 64);
+        for i in 0..6 {
+            stream.insert(i, token(i as f32), i % 4 == 0);
+        }
+        assert_eq!((stream.first_frame(), stream.bytes()), (Some(0), 6 * 64));
+
+        // Going over drops frames 0-3 together, keeping keyframe 4 first
+        stream.insert(6, token(6.0), false);
+        assert_eq!(stream.first_frame(), Some(4));
+        assert_eq!(stream.keyframe_before(5), Some(4));
+        assert_eq!(stream.bytes(), 3 * 64);
+        assert_eq!(stream.frame_count(), 7);
+
+        // Without a later keyframe the oldest token goes on its own
+        for i in 7..11 {
+            stream.insert(i, token(i as f32), false);
+        }
+        assert_eq!(stream.first_frame(), Some(5));
+        assert_eq!(stream.bytes(), 6 * 64);
+
+        // Replacing a token doesn't count it twice
+        stream.insert(10, token(0.0), false);
+        assert_eq!(stream.bytes(), 6 * 64);
+    }
+
+    #[test]
+    fn test_quantize_token() {
+        let token = [0.0, 10.0, 20.0, 255.0];
+        // 8 bits over 0-255 is a step of 1, so integer values survive
+        assert_eq!(quantize_token(&token, 8).unwrap(), token);
+        assert_eq!(quantize_token(&token, 1).unwrap(), [0.0, 0.0, 0.0, 255.0]);
+        assert_eq!(quantize_token(&[7.0; 4], 2).unwrap(), [7.0; 4]);
+        assert!(quantize_token(&token, 0).is_err());
+        assert_eq!(quantized_token_bits(16, 4), 128);
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_token_size() {
+        assert!(decode_token(&[1.0; 3], 2, 2).is_err());
+    }
+}
@@ -1,12 +1,116 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use web_sys::{
+    HtmlCanvasElement, ImageBitmap, WebGl2RenderingContext, WebGlFramebuffer, WebGlProgram, WebGlShader,
+    WebGlTexture, WebGlUniformLocation,
+};
 use wasm_bindgen::JsCast;
+use serde::Serialize;
+use super::frame::{Frame, PixelFormat, YuvTransform};
+use super::gpu_filter::{GpuFilter, FILTER_VERTEX_SHADER};
+
+type Gl = WebGl2RenderingContext;
+
+// WebGL context creation attributes
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContextAttributes {
+    // Keep the drawing buffer after compositing, so `capture` can read it
+    // back outside the task that drew it
+    preserve_drawing_buffer: bool,
+}
+
+const CONTEXT_ATTRIBUTES: ContextAttributes = ContextAttributes { preserve_drawing_buffer: true };
+
+// Full-screen triangle generated from gl_VertexID, no vertex buffers
+const VERTEX_SHADER: &str = r#"#version 300 es
+out vec2 uv;
+void main() {
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    uv = vec2(corner.x, 1.0 - corner.y);
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const RGBA_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D frame;
+in vec2 uv;
+out vec4 outColor;
+void main() {
+    outColor = texture(frame, uv);
+}
+"#;
+
+// I420 samples U and V from separate planes, NV12 from one RG plane
+const YUV_SHADER: &str = r#"#version 300 es
+precision highp float;
+uniform sampler2D planeY;
+uniform sampler2D planeU;
+uniform sampler2D planeV;
+uniform bool interleaved;
+uniform mat3 yuvToRgb;
+uniform vec3 yuvOffset;
+in vec2 uv;
+out vec4 outColor;
+void main() {
+    vec3 yuv;
+    yuv.x = texture(planeY, uv).r;
+    yuv.yz = interleaved
+        ? texture(planeU, uv).rg
+        : vec2(texture(planeU, uv).r, texture(planeV, uv).r);
+    outColor = vec4(clamp(yuvToRgb * (yuv - yuvOffset), 0.0, 1.0), 1.0);
+}
+"#;
+
+struct YuvUniforms {
+    interleaved: Option<WebGlUniformLocation>,
+    matrix: Option<WebGlUniformLocation>,
+    offset: Option<WebGlUniformLocation>,
+}
+
+// Texture for one plane, reallocated only when its size or format changes
+struct PlaneTexture {
+    texture: WebGlTexture,
+    shape: Option<(i32, i32, u32)>,
+}
+
+// Compiled GpuFilter
+struct FilterPass {
+    filter: GpuFilter,
+    program: WebGlProgram,
+    shader: WebGlShader,
+    texel: Option<WebGlUniformLocation>,
+    params: Option<WebGlUniformLocation>,
+}
+
+// Offscreen texture that filter passes render into
+struct RenderTarget {
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture,
+    size: (i32, i32),
+}
+
+// Which program draws the uploaded frame
+#[derive(Clone, Copy)]
+enum Source {
+    Rgba,
+    Yuv,
+}
 
 pub struct WebGLDecoder {
+    canvas: HtmlCanvasElement,
     context: WebGl2RenderingContext,
     program: WebGlProgram,
+    yuv_program: WebGlProgram,
+    yuv_uniforms: YuvUniforms,
     vertex_shader: WebGlShader,
     fragment_shader: WebGlShader,
+    yuv_shader: WebGlShader,
+    planes: [PlaneTexture; 3],
+    filter_vertex_shader: WebGlShader,
+    filters: Vec<FilterPass>,
+    targets: [Option<RenderTarget>; 2],
+    overlay: PlaneTexture,
 }
 
 impl WebGLDecoder {
@@ -17,44 +121,437 @@ impl WebGLDecoder {
             .create_element("canvas")?
             .dyn_into::<web_sys::HtmlCanvasElement>()?;
 
+        Self::from_canvas(canvas)
+    }
+
+    pub fn from_canvas(canvas: HtmlCanvasElement) -> Result<Self, JsValue> {
+        let attributes = serde_wasm_bindgen::to_value(&CONTEXT_ATTRIBUTES)?;
         let context = canvas
-            .get_context("webgl2")?
-            .unwrap()
+            .get_context_with_context_options("webgl2", &attributes)?
+            .ok_or_else(|| JsValue::from_str("WebGL2 not available"))?
             .dyn_into::<WebGl2RenderingContext>()?;
 
-        // Initialize shaders and program
-        let vertex_shader = compile_shader(
-            &context,
-            WebGl2RenderingContext::VERTEX_SHADER,
-            r#"#version 300 es
-            in vec4 position;
-            void main() {
-                gl_Position = position;
-            }
-            "#,
-        )?;
-
-        let fragment_shader = compile_shader(
-            &context,
-            WebGl2RenderingContext::FRAGMENT_SHADER,
-            r#"#version 300 es
-            precision highp float;
-            out vec4 outColor;
-            void main() {
-                outColor = vec4(1.0, 0.0, 0.0, 1.0);
-            }
-            "#,
-        )?;
+        // Initialize shaders and programs
+        let vertex_shader = compile_shader(&context, Gl::VERTEX_SHADER, VERTEX_SHADER)?;
+        let fragment_shader = compile_shader(&context, Gl::FRAGMENT_SHADER, RGBA_SHADER)?;
+        let yuv_shader = compile_shader(&context, Gl::FRAGMENT_SHADER, YUV_SHADER)?;
 
         let program = link_program(&context, &vertex_shader, &fragment_shader)?;
+        let yuv_program = link_program(&context, &vertex_shader, &yuv_shader)?;
+        let filter_vertex_shader = compile_shader(&context, Gl::VERTEX_SHADER, FILTER_VERTEX_SHADER)?;
+
+        // Plane i is bound to texture unit i
+        context.use_program(Some(&yuv_program));
+        for (unit, name) in ["planeY", "planeU", "planeV"].iter().enumerate() {
+            context.uniform1i(context.get_uniform_location(&yuv_program, name).as_ref(), unit as i32);
+        }
+        let yuv_uniforms = YuvUniforms {
+            interleaved: context.get_uniform_location(&yuv_program, "interleaved"),
+            matrix: context.get_uniform_location(&yuv_program, "yuvToRgb"),
+            offset: context.get_uniform_location(&yuv_program, "yuvOffset"),
+        };
+
+        let planes = [
+            create_plane(&context)?,
+            create_plane(&context)?,
+            create_plane(&context)?,
+        ];
+
+        let overlay = create_plane(&context)?;
+
+        // Single-channel planes have rows of any width
+        context.pixel_storei(Gl::UNPACK_ALIGNMENT, 1);
 
         Ok(Self {
+            canvas,
             context,
             program,
+            yuv_program,
+            yuv_uniforms,
             vertex_shader,
             fragment_shader,
+            yuv_shader,
+            planes,
+            filter_vertex_shader,
+            filters: Vec::new(),
+            targets: [None, None],
+            overlay,
         })
     }
+
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+
+    // Uploads the frame's planes and draws it aspect-fit into the canvas
+    pub fn present(&mut self, frame: &Frame) -> Result<(), JsValue> {
+        frame.validate().map_err(|e| JsValue::from_str(&e))?;
+
+        let (width, height) = (frame.width, frame.height);
+        match frame.format {
+            PixelFormat::Rgba => {
+                self.upload_plane(0, width, height, Gl::RGBA, &frame.data)?;
+                self.draw(Source::Rgba, width, height, 1.0)?;
+            }
+            PixelFormat::I420 | PixelFormat::Nv12 => {
+                let (chroma_width, chroma_height) = PixelFormat::chroma_size(width, height);
+                let (y, u, v) = frame.yuv_planes().map_err(|e| JsValue::from_str(&e))?;
+                let interleaved = frame.format == PixelFormat::Nv12;
+
+                self.upload_plane(0, width, height, Gl::RED, y)?;
+                if interleaved {
+                    self.upload_plane(1, chroma_width, chroma_height, Gl::RG, u)?;
+                } else {
+                    self.upload_plane(1, chroma_width, chroma_height, Gl::RED, u)?;
+                    self.upload_plane(2, chroma_width, chroma_height, Gl::RED, v)?;
+                }
+
+                let transform = YuvTransform::new(frame.matrix, frame.range);
+                let gl = &self.context;
+                gl.use_program(Some(&self.yuv_program));
+                gl.uniform1i(self.yuv_uniforms.interleaved.as_ref(), interleaved as i32);
+                gl.uniform_matrix3fv_with_f32_array(
+                    self.yuv_uniforms.matrix.as_ref(),
+                    true,
+                    transform.matrix.as_flattened(),
+                );
+                gl.uniform3fv_with_f32_array(self.yuv_uniforms.offset.as_ref(), &transform.offset);
+
+                self.draw(Source::Yuv, width, height, 1.0)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Draws a bitmap over the current image with the given opacity
+    pub fn present_bitmap(&mut self, bitmap: &ImageBitmap, alpha: f64) -> Result<(), JsValue> {
+        let (width, height) = (bitmap.width() as i32, bitmap.height() as i32);
+        let gl = &self.context;
+        let plane = &mut self.planes[0];
+        gl.active_texture(Gl::TEXTURE0);
+        gl.bind_texture(Gl::TEXTURE_2D, Some(&plane.texture));
+
+        let shape = (width, height, Gl::RGBA8);
+        if plane.shape == Some(shape) {
+            gl.tex_sub_image_2d_with_u32_and_u32_and_image_bitmap(
+                Gl::TEXTURE_2D, 0, 0, 0, Gl::RGBA, Gl::UNSIGNED_BYTE, bitmap,
+            )?;
+        } else {
+            gl.tex_image_2d_with_u32_and_u32_and_image_bitmap(
+                Gl::TEXTURE_2D, 0, Gl::RGBA8 as i32, Gl::RGBA, Gl::UNSIGNED_BYTE, bitmap,
+            )?;
+            plane.shape = Some(shape);
+        }
+
+        self.draw(Source::Rgba, width as usize, height as usize, alpha as f32)
+    }
+
+    // Blends a canvas-sized RGBA overlay over the current image, skipping the
+    // filter chain
+    pub fn present_overlay(&mut self, overlay: &Frame) -> Result<(), JsValue> {
+        let overlay = overlay.as_rgba().map_err(|e| JsValue::from_str(&e))?;
+        let gl = &self.context;
+        gl.active_texture(Gl::TEXTURE0);
+        upload_texture(gl, &mut self.overlay, overlay.width, overlay.height, Gl::RGBA, &overlay.data)?;
+
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, None);
+        gl.viewport(0, 0, overlay.width as i32, overlay.height as i32);
+        gl.use_program(Some(&self.program));
+        gl.enable(Gl::BLEND);
+        // Keep the destination alpha so the canvas stays opaque
+        gl.blend_func_separate(Gl::SRC_ALPHA, Gl::ONE_MINUS_SRC_ALPHA, Gl::ZERO, Gl::ONE);
+        gl.draw_arrays(Gl::TRIANGLES, 0, 3);
+        gl.disable(Gl::BLEND);
+        Ok(())
+    }
+
+    // Replaces the post-processing chain. Passes run in order on every
+    // presented frame; an empty chain draws frames directly.
+    pub fn set_filters(&mut self, filters: &[GpuFilter]) -> Result<(), JsValue> {
+        for filter in filters {
+            filter.validate().map_err(|e| JsValue::from_str(&e))?;
+        }
+
+        let mut passes = Vec::with_capacity(filters.len());
+        for filter in filters {
+            match self.compile_pass(*filter) {
+                Ok(pass) => passes.push(pass),
+                Err(e) => {
+                    passes.iter().for_each(|pass| self.delete_pass(pass));
+                    return Err(e);
+                }
+            }
+        }
+
+        let old = std::mem::replace(&mut self.filters, passes);
+        old.iter().for_each(|pass| self.delete_pass(pass));
+        Ok(())
+    }
+
+    pub fn filters(&self) -> Vec<GpuFilter> {
+        self.filters.iter().map(|pass| pass.filter).collect()
+    }
+
+    pub fn resize(&self, width: u32, height: u32) {
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+    }
+
+    pub fn clear(&self) {
+        self.context.clear_color(0.0, 0.0, 0.0, 1.0);
+        self.context.clear(Gl::COLOR_BUFFER_BIT);
+    }
+
+    // Reads back the drawing buffer as a top-down RGBA frame
+    pub fn capture(&self) -> Result<Frame, JsValue> {
+        let (width, height) = (self.canvas.width() as usize, self.canvas.height() as usize);
+        let mut pixels = vec![0u8; width * height * 4];
+        self.context.read_pixels_with_opt_u8_array(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            Gl::RGBA,
+            Gl::UNSIGNED_BYTE,
+            Some(&mut pixels),
+        )?;
+
+        // GL rows start at the bottom
+        let mut frame = Frame::new(width, height);
+        let row = width * 4;
+        for (y, line) in pixels.chunks_exact(row).enumerate() {
+            let dst = (height - 1 - y) * row;
+            frame.data[dst..dst + row].copy_from_slice(line);
+        }
+        Ok(frame)
+    }
+
+    // Uploads into plane `index` (texture unit `index`), reusing its storage
+    // with texSubImage2D while the shape stays the same
+    fn upload_plane(&mut self, index: usize, width: usize, height: usize, format: u32, data: &[u8]) -> Result<(), JsValue> {
+        self.context.active_texture(Gl::TEXTURE0 + index as u32);
+        upload_texture(&self.context, &mut self.planes[index], width, height, format, data)
+    }
+
+    // Draws the uploaded frame into the largest centered rect with its aspect
+    // ratio, through the filter chain if there is one
+    fn draw(&mut self, source: Source, width: usize, height: usize, alpha: f32) -> Result<(), JsValue> {
+        let fit = fit_rect(width as u32, height as u32, self.canvas.width(), self.canvas.height());
+        if self.filters.is_empty() {
+            self.draw_to_canvas(self.source_program(source), fit, alpha);
+            return Ok(());
+        }
+
+        // Convert the frame at its own size, then run each pass on the
+        // previous output. The last pass draws to the canvas.
+        let mut size = (width as i32, height as i32);
+        self.ensure_target(0, size)?;
+        self.draw_to_target(0, self.source_program(source));
+
+        let mut input = 0;
+        for index in 0..self.filters.len() {
+            let last = index + 1 == self.filters.len();
+            let output_size = if self.filters[index].filter.upscales() { (fit.2, fit.3) } else { size };
+            if !last {
+                self.ensure_target(1 - input, output_size)?;
+            }
+
+            let gl = &self.context;
+            let pass = &self.filters[index];
+            gl.use_program(Some(&pass.program));
+            gl.uniform2f(pass.texel.as_ref(), 1.0 / size.0 as f32, 1.0 / size.1 as f32);
+            gl.uniform4fv_with_f32_array(pass.params.as_ref(), &pass.filter.params());
+
+            let Some(target) = &self.targets[input] else { break };
+            gl.active_texture(Gl::TEXTURE0);
+            gl.bind_texture(Gl::TEXTURE_2D, Some(&target.texture));
+
+            if last {
+                self.draw_to_canvas(&pass.program, fit, alpha);
+            } else {
+                self.draw_to_target(1 - input, &pass.program);
+            }
+
+            input = 1 - input;
+            size = output_size;
+        }
+        Ok(())
+    }
+
+    fn source_program(&self, source: Source) -> &WebGlProgram {
+        match source {
+            Source::Rgba => &self.program,
+            Source::Yuv => &self.yuv_program,
+        }
+    }
+
+    // Opaque draws clear the letterbox bars first
+    fn draw_to_canvas(&self, program: &WebGlProgram, fit: (i32, i32, i32, i32), alpha: f32) {
+        let gl = &self.context;
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, None);
+
+        if alpha >= 1.0 {
+            gl.viewport(0, 0, self.canvas.width() as i32, self.canvas.height() as i32);
+            self.clear();
+        }
+
+        let (x, y, fit_width, fit_height) = fit;
+        gl.viewport(x, y, fit_width, fit_height);
+        gl.use_program(Some(program));
+
+        if alpha < 1.0 {
+            gl.enable(Gl::BLEND);
+            gl.blend_color(0.0, 0.0, 0.0, alpha);
+            gl.blend_func(Gl::CONSTANT_ALPHA, Gl::ONE_MINUS_CONSTANT_ALPHA);
+        }
+        gl.draw_arrays(Gl::TRIANGLES, 0, 3);
+        gl.disable(Gl::BLEND);
+    }
+
+    fn draw_to_target(&self, index: usize, program: &WebGlProgram) {
+        let Some(target) = &self.targets[index] else { return };
+        let gl = &self.context;
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, Some(&target.framebuffer));
+        gl.viewport(0, 0, target.size.0, target.size.1);
+        gl.use_program(Some(program));
+        gl.draw_arrays(Gl::TRIANGLES, 0, 3);
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, None);
+    }
+
+    // (Re)allocates render target `index` when its size changes
+    fn ensure_target(&mut self, index: usize, size: (i32, i32)) -> Result<(), JsValue> {
+        if self.targets[index].as_ref().is_some_and(|target| target.size == size) {
+            return Ok(());
+        }
+
+        let gl = &self.context;
+        if let Some(old) = self.targets[index].take() {
+            gl.delete_framebuffer(Some(&old.framebuffer));
+            gl.delete_texture(Some(&old.texture));
+        }
+
+        // Allocate on a unit the frame planes don't use
+        gl.active_texture(Gl::TEXTURE3);
+        let PlaneTexture { texture, .. } = create_plane(gl)?;
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D, 0, Gl::RGBA8 as i32, size.0, size.1, 0, Gl::RGBA, Gl::UNSIGNED_BYTE, None,
+        )?;
+
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or_else(|| JsValue::from_str("Unable to create framebuffer"))?;
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, Some(&framebuffer));
+        gl.framebuffer_texture_2d(Gl::FRAMEBUFFER, Gl::COLOR_ATTACHMENT0, Gl::TEXTURE_2D, Some(&texture), 0);
+        gl.bind_framebuffer(Gl::FRAMEBUFFER, None);
+
+        self.targets[index] = Some(RenderTarget { framebuffer, texture, size });
+        Ok(())
+    }
+
+    fn compile_pass(&self, filter: GpuFilter) -> Result<FilterPass, JsValue> {
+        let shader = compile_shader(&self.context, Gl::FRAGMENT_SHADER, filter.fragment_shader())?;
+        let program = match link_program(&self.context, &self.filter_vertex_shader, &shader) {
+            Ok(program) => program,
+            Err(e) => {
+                self.context.delete_shader(Some(&shader));
+                return Err(e.into());
+            }
+        };
+
+        Ok(FilterPass {
+            filter,
+            texel: self.context.get_uniform_location(&program, "texel"),
+            params: self.context.get_uniform_location(&program, "params"),
+            program,
+            shader,
+        })
+    }
+
+    fn delete_pass(&self, pass: &FilterPass) {
+        self.context.delete_program(Some(&pass.program));
+        self.context.delete_shader(Some(&pass.shader));
+    }
+}
+
+impl Drop for WebGLDecoder {
+    fn drop(&mut self) {
+        for plane in &self.planes {
+            self.context.delete_texture(Some(&plane.texture));
+        }
+        self.context.delete_texture(Some(&self.overlay.texture));
+        for target in self.targets.iter().flatten() {
+            self.context.delete_framebuffer(Some(&target.framebuffer));
+            self.context.delete_texture(Some(&target.texture));
+        }
+        for pass in &self.filters {
+            self.delete_pass(pass);
+        }
+        self.context.delete_shader(Some(&self.filter_vertex_shader));
+        self.context.delete_program(Some(&self.program));
+        self.context.delete_program(Some(&self.yuv_program));
+        self.context.delete_shader(Some(&self.vertex_shader));
+        self.context.delete_shader(Some(&self.fragment_shader));
+        self.context.delete_shader(Some(&self.yuv_shader));
+    }
+}
+
+// Largest rect with the source aspect ratio centered in the destination,
+// as (x, y, width, height)
+fn fit_rect(src_width: u32, src_height: u32, dst_width: u32, dst_height: u32) -> (i32, i32, i32, i32) {
+    if src_width == 0 || src_height == 0 {
+        return (0, 0, dst_width as i32, dst_height as i32);
+    }
+
+    let scale = (dst_width as f64 / src_width as f64).min(dst_height as f64 / src_height as f64);
+    let width = (src_width as f64 * scale).round() as i32;
+    let height = (src_height as f64 * scale).round() as i32;
+    ((dst_width as i32 - width) / 2, (dst_height as i32 - height) / 2, width, height)
+}
+
+fn create_plane(context: &WebGl2RenderingContext) -> Result<PlaneTexture, JsValue> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| JsValue::from_str("Unable to create texture"))?;
+    context.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+    for (param, value) in [
+        (Gl::TEXTURE_MIN_FILTER, Gl::LINEAR),
+        (Gl::TEXTURE_MAG_FILTER, Gl::LINEAR),
+        (Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE),
+        (Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE),
+    ] {
+        context.tex_parameteri(Gl::TEXTURE_2D, param, value as i32);
+    }
+    Ok(PlaneTexture { texture, shape: None })
+}
+
+// Binds `plane` to the active unit and uploads into it
+fn upload_texture(
+    context: &WebGl2RenderingContext,
+    plane: &mut PlaneTexture,
+    width: usize,
+    height: usize,
+    format: u32,
+    data: &[u8],
+) -> Result<(), JsValue> {
+    let internal_format = match format {
+        Gl::RED => Gl::R8,
+        Gl::RG => Gl::RG8,
+        _ => Gl::RGBA8,
+    };
+    context.bind_texture(Gl::TEXTURE_2D, Some(&plane.texture));
+
+    let shape = (width as i32, height as i32, internal_format);
+    if plane.shape == Some(shape) {
+        context.tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D, 0, 0, 0, shape.0, shape.1, format, Gl::UNSIGNED_BYTE, Some(data),
+        )?;
+    } else {
+        context.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D, 0, internal_format as i32, shape.0, shape.1, 0, format, Gl::UNSIGNED_BYTE, Some(data),
+        )?;
+        plane.shape = Some(shape);
+    }
+    Ok(())
 }
 
 fn compile_shader(
@@ -105,4 +602,24 @@ fn link_program(
             .get_program_info_log(&program)
             .unwrap_or_else(|| String::from("Unknown error creating program")))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_rect_letterboxes() {
+        // Square content on a wide canvas gets bars left and right
+        assert_eq!(fit_rect(256, 256, 1920, 1080), (420, 0, 1080, 1080));
+        // Wide content on a square canvas gets bars top and bottom
+        assert_eq!(fit_rect(1920, 1080, 512, 512), (0, 112, 512, 288));
+        assert_eq!(fit_rect(0, 0, 64, 32), (0, 0, 64, 32));
+    }
+
+    #[test]
+    fn test_context_keeps_drawing_buffer_for_capture() {
+        let attributes = serde_json::to_value(&CONTEXT_ATTRIBUTES).unwrap();
+        assert_eq!(attributes, serde_json::json!({ "preserveDrawingBuffer": true }));
+    }
+}
@@ -1,110 +1,277 @@
 use wasm_bindgen::prelude::*;
-use serde::{Serialize, Deserialize};
-use web_sys::WorkerGlobalScope;
-
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
-pub enum DecoderStatus {
-    Idle = 0,
-    Initializing = 1,
-    Inited = 2,
-    Ready = 3,
-    Open = 4,
-    Pause = 5,
-    Closed = 6,
-}
-
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
-pub enum DecodeMessage {
-    DecoderCreated = 0,
-    DecoderInit = 1,
-    DecoderInited = 2,
-    WasmLoaded = 3,
-    DecoderReady = 4,
-    DecoderOpenError = 5,
-    DecoderStart = 6,
-    DecoderStarted = 7,
-    DecoderPause = 8,
-    DecoderPaused = 9,
-    DecoderClose = 10,
-    DecoderClosed = 11,
-    DecodeVideoBuffer = 12,
-    DecodedVideoFrame = 13,
-}
+use wasm_bindgen::JsCast;
+use web_sys::DedicatedWorkerGlobalScope;
+use log::warn;
+use crate::wasm::bindings::IMFDecoder;
+use crate::wasm::state::token_payload_bytes;
+use super::lifecycle::DecoderEvent;
+use super::protocol::{
+    DecodeMessage, DecoderOptions, DecoderSettings, DecoderStatus, ErrorDetails, ErrorKind, FramePayload, StatusSnapshot,
+    TokenPacket, WorkerMessage,
+};
 
 #[wasm_bindgen]
 pub struct DecoderWorker {
     status: DecoderStatus,
-    decoder: IMFDecoder,
-    scope: WorkerGlobalScope,
+    decoder: Option<IMFDecoder>,       // Created by DecoderInit with the requested size
+    scope: DedicatedWorkerGlobalScope,
+    frames_decoded: u64,
+    last_frame_index: Option<usize>,
 }
 
 #[wasm_bindgen]
 impl DecoderWorker {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Result<DecoderWorker, JsValue> {
-        let scope = js_sys::global().unchecked_into::<WorkerGlobalScope>();
-        let decoder = IMFDecoder::new(1920, 1080)?;
-        
+        let scope = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+
         let worker = DecoderWorker {
             status: DecoderStatus::Idle,
-            decoder,
+            decoder: None,
             scope,
+            frames_decoded: 0,
+            last_frame_index: None,
         };
 
-        worker.post_message(DecodeMessage::DecoderCreated);
+        worker.post(WorkerMessage::new(DecodeMessage::DecoderCreated));
         Ok(worker)
     }
 
-    fn post_message(&self, msg: DecodeMessage) {
-        let msg_val = serde_wasm_bindgen::to_value(&msg).unwrap();
-        self.scope.post_message(&msg_val).unwrap();
+    // Entry point for `self.onmessage`: parses a WorkerMessage, runs it and
+    // posts the replies tagged with its request id
+    #[wasm_bindgen]
+    pub fn handle_message(&mut self, data: JsValue) {
+        // Token sizes for the bitrate are taken from the arrays as received
+        let payload = js_sys::Reflect::get(&data, &"message".into())
+            .and_then(|message| js_sys::Reflect::get(&message, &"data".into()))
+            .unwrap_or(JsValue::UNDEFINED);
+        let request: WorkerMessage = match serde_wasm_bindgen::from_value(data) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = ErrorDetails::new(ErrorKind::Protocol, format!("Malformed message: {}", e));
+                self.post(WorkerMessage::new(DecodeMessage::DecoderError(error)));
+                return;
+            }
+        };
+
+        // Failures to open get their own message so the main thread can tell
+        // them apart from errors while running. An init the lifecycle rejects
+        // never touched the decoder, so it's an ordinary error.
+        let id = request.request_id;
+        let opening = matches!(request.message, DecodeMessage::DecoderInit(_));
+        let result = request.check_version().and_then(|_| self.dispatch(id, request.message, &payload));
+        if let Err(error) = result {
+            let reply = if opening && error.transition.is_none() {
+                DecodeMessage::DecoderOpenError(error)
+            } else {
+                DecodeMessage::DecoderError(error)
+            };
+            self.post(WorkerMessage::reply(id, reply));
+        }
     }
 
     #[wasm_bindgen]
-    pub fn initialize(&mut self) -> Result<(), JsValue> {
-        self.status = DecoderStatus::Initializing;
-        self.post_message(DecodeMessage::DecoderInit);
-        
-        // Initialize decoder
-        self.decoder.test();
-        
-        self.status = DecoderStatus::Inited;
-        self.post_message(DecodeMessage::DecoderInited);
-        Ok(())
+    pub fn initialize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        self.open(None, DecoderOptions::new(width, height)).map_err(to_js)
     }
 
     #[wasm_bindgen]
     pub fn start(&mut self) -> Result<(), JsValue> {
-        if self.status != DecoderStatus::Ready {
-            return Err(JsValue::from_str("Decoder not ready"));
+        self.run(None).map_err(to_js)
+    }
+
+    #[wasm_bindgen]
+    pub fn pause(&mut self) -> Result<(), JsValue> {
+        self.suspend(None).map_err(to_js)
+    }
+
+    // Releases the decoder; only a reset allows opening it again
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        self.shut_down(None).map_err(to_js)
+    }
+
+    // Releases the decoder from any status and goes back to Idle
+    #[wasm_bindgen]
+    pub fn reset(&mut self) {
+        self.restart(None);
+    }
+
+    #[wasm_bindgen]
+    pub fn process_frame(&mut self, frame_data: JsValue) -> Result<(), JsValue> {
+        let payload_bytes = token_payload_bytes(&frame_data);
+        let tokens: Vec<TokenPacket> = serde_wasm_bindgen::from_value(frame_data)?;
+        self.decode(None, tokens, payload_bytes).map_err(to_js)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_status(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.snapshot())?)
+    }
+}
+
+impl DecoderWorker {
+    fn dispatch(&mut self, id: Option<u32>, message: DecodeMessage, payload: &JsValue) -> Result<(), ErrorDetails> {
+        match message {
+            DecodeMessage::DecoderInit(options) => self.open(id, options),
+            DecodeMessage::DecoderStart => self.run(id),
+            DecodeMessage::DecoderPause => self.suspend(id),
+            DecodeMessage::DecoderClose => self.shut_down(id),
+            DecodeMessage::DecoderReset => {
+                self.restart(id);
+                Ok(())
+            }
+            DecodeMessage::DecodeVideoBuffer(tokens) => self.decode(id, tokens, token_payload_bytes(payload)),
+            DecodeMessage::RequestStats => {
+                self.post(WorkerMessage::reply(id, DecodeMessage::DecoderStats(self.snapshot())));
+                Ok(())
+            }
+            other => Err(ErrorDetails::new(
+                ErrorKind::Protocol,
+                format!("{:?} is sent by the worker, not to it", other),
+            )),
         }
+    }
 
-        self.status = DecoderStatus::Open;
-        self.post_message(DecodeMessage::DecoderStarted);
+    // Moves to the status `event` leads to, or rejects it without changing anything
+    fn transition(&mut self, event: DecoderEvent) -> Result<(), ErrorDetails> {
+        self.status = self.status.apply(event)?;
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn pause(&mut self) -> Result<(), JsValue> {
-        if self.status != DecoderStatus::Open {
-            return Err(JsValue::from_str("Decoder not running"));
+    // A new decoder needs nothing beyond construction and its settings, so a
+    // successful open goes straight on to Ready
+    fn open(&mut self, id: Option<u32>, options: DecoderOptions) -> Result<(), ErrorDetails> {
+        self.transition(DecoderEvent::Initialize)?;
+        match IMFDecoder::new(options.width, options.height) {
+            Ok(mut decoder) => {
+                apply_settings(&mut decoder, &options.settings);
+                self.decoder = Some(decoder);
+                self.transition(DecoderEvent::Initialized)?;
+                self.post(WorkerMessage::reply(id, DecodeMessage::DecoderInited));
+                self.transition(DecoderEvent::Prepared)?;
+                self.post(WorkerMessage::reply(id, DecodeMessage::DecoderReady));
+                Ok(())
+            }
+            Err(e) => {
+                self.transition(DecoderEvent::Fail)?;
+                Err(ErrorDetails::new(ErrorKind::Decode, js_message(&e)))
+            }
         }
+    }
 
-        self.status = DecoderStatus::Pause;
-        self.post_message(DecodeMessage::DecoderPaused);
+    fn run(&mut self, id: Option<u32>) -> Result<(), ErrorDetails> {
+        self.transition(DecoderEvent::Start)?;
+        self.post(WorkerMessage::reply(id, DecodeMessage::DecoderStarted));
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn process_frame(&mut self, frame_data: JsValue) -> Result<(), JsValue> {
-        if self.status != DecoderStatus::Open {
-            return Err(JsValue::from_str("Decoder not running"));
+    fn suspend(&mut self, id: Option<u32>) -> Result<(), ErrorDetails> {
+        self.transition(DecoderEvent::Pause)?;
+        self.post(WorkerMessage::reply(id, DecodeMessage::DecoderPaused));
+        Ok(())
+    }
+
+    fn shut_down(&mut self, id: Option<u32>) -> Result<(), ErrorDetails> {
+        self.transition(DecoderEvent::Close)?;
+        self.release();
+        self.post(WorkerMessage::reply(id, DecodeMessage::DecoderClosed));
+        Ok(())
+    }
+
+    // Reset is legal from every status
+    fn restart(&mut self, id: Option<u32>) {
+        self.status = DecoderStatus::Idle;
+        self.release();
+        self.frames_decoded = 0;
+        self.last_frame_index = None;
+        self.post(WorkerMessage::reply(id, DecodeMessage::DecoderResetDone));
+    }
+
+    // Dropping the decoder stops its render loop and frees its queues,
+    // token stream and GPU resources
+    fn release(&mut self) {
+        self.decoder = None;
+    }
+
+    // Decodes every packet in the buffer and posts one DecodedVideoFrame per
+    // output frame; nothing stays queued in the worker afterwards
+    fn decode(&mut self, id: Option<u32>, tokens: Vec<TokenPacket>, payload_bytes: usize) -> Result<(), ErrorDetails> {
+        if !self.status.is_decoding() {
+            return Err(ErrorDetails::new(
+                ErrorKind::InvalidState,
+                format!("Can't decode while the decoder is {:?}", self.status),
+            ));
         }
+        let decoder = self.decoder
+            .as_mut()
+            .ok_or_else(|| ErrorDetails::new(ErrorKind::InvalidState, "Decoder not initialized"))?;
+
+        decoder.push_tokens(tokens, payload_bytes).map_err(|e| ErrorDetails::new(ErrorKind::Decode, js_message(&e)))?;
+        let frames = decoder.decode_pending();
 
-        self.decoder.process_tokens(frame_data)?;
-        self.decoder.process_batch()?;
-        
-        self.post_message(DecodeMessage::DecodedVideoFrame);
+        for (index, frame) in frames {
+            self.frames_decoded += 1;
+            self.last_frame_index = Some(index);
+            self.post(WorkerMessage::reply(id, DecodeMessage::DecodedVideoFrame(FramePayload::new(index, frame))));
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn snapshot(&self) -> StatusSnapshot {
+        let queue = self.decoder.as_ref().map(|decoder| decoder.queue_stats());
+        StatusSnapshot {
+            status: self.status,
+            frames_decoded: self.frames_decoded,
+            frames_dropped: queue.as_ref().map_or(0, |stats| stats.frames_dropped),
+            last_frame_index: self.last_frame_index,
+            queue_depths: queue.map_or([0; 3], |stats| {
+                [stats.input_queue_size, stats.processing_queue_size, stats.output_queue_size]
+            }),
+        }
+    }
+
+    // Frame pixels are transferred to the main thread instead of copied
+    fn post(&self, message: WorkerMessage) {
+        let value = match serde_wasm_bindgen::to_value(&message) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Failed to serialize worker message: {}", e);
+                return;
+            }
+        };
+
+        let result = match &message.message {
+            DecodeMessage::DecodedVideoFrame(_) => {
+                let buffer = js_sys::Reflect::get(&value, &"message".into())
+                    .and_then(|message| js_sys::Reflect::get(&message, &"data".into()))
+                    .and_then(|frame| js_sys::Reflect::get(&frame, &"data".into()))
+                    .map(|pixels| pixels.unchecked_into::<js_sys::Uint8Array>().buffer());
+                match buffer {
+                    Ok(buffer) => self.scope.post_message_with_transfer(&value, &js_sys::Array::of1(&buffer)),
+                    Err(e) => Err(e),
+                }
+            }
+            _ => self.scope.post_message(&value),
+        };
+        if let Err(e) = result {
+            warn!("Failed to post worker message: {:?}", e);
+        }
+    }
+}
+
+fn apply_settings(decoder: &mut IMFDecoder, settings: &DecoderSettings) {
+    if let Some(fps) = settings.target_fps {
+        decoder.set_target_fps(fps);
+    }
+    decoder.set_adaptive_fps(settings.adaptive_fps);
+    decoder.set_debug_mode(settings.debug_mode);
+}
+
+fn js_message(value: &JsValue) -> String {
+    value.as_string().unwrap_or_else(|| format!("{:?}", value))
+}
+
+fn to_js(error: ErrorDetails) -> JsValue {
+    JsValue::from_str(&error.message)
+}
@@ -1,6 +1,9 @@
 use wasm_bindgen::prelude::*;
 
 pub mod decoder;
+pub mod player;
+pub mod quality;
+pub mod render;
 pub mod utils;
 pub mod wasm;
 
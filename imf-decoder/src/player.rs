@@ -1,74 +1,458 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
-use web_sys::{Worker, MessageEvent};
+use wasm_bindgen::JsCast;
+use web_sys::{Worker, WorkerOptions, WorkerType, MessageEvent};
+use serde::{Serialize, Deserialize};
+use log::warn;
+use crate::decoder::{DecodeMessage, DecoderOptions, DecoderSettings, ErrorDetails, ErrorKind, WorkerMessage};
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlayerStatus {
     Idle = 0,
     Ready = 1,
     Playing = 2,
     Pause = 3,
     Destroyed = 4,
+    Closed = 5, // Decoder released; only reset() leads back to Idle
+    Error = 6,  // Decoder failed to open; close() or reset() it
+}
+
+// How a Player starts its worker and the decoder inside it. From JS:
+// `{ width, height, workerUrl?, module?, settings?: { targetFps?, adaptiveFps?, debugMode? } }`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerConfig {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_worker_url")]
+    pub worker_url: String,
+    // Load the script as an ES module worker instead of a classic one
+    #[serde(default)]
+    pub module: bool,
+    // Sent to the worker with DecoderInit
+    #[serde(default)]
+    pub settings: DecoderSettings,
+}
+
+fn default_worker_url() -> String { "./decoder.worker.js".to_string() }
+
+impl PlayerConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            worker_url: default_worker_url(),
+            module: false,
+            settings: DecoderSettings::default(),
+        }
+    }
+
+    fn spawn_worker(&self) -> Result<Worker, JsValue> {
+        let options = WorkerOptions::new();
+        options.set_type(if self.module { WorkerType::Module } else { WorkerType::Classic });
+        Worker::new_with_options(&self.worker_url, &options)
+    }
+}
+
+// Which JS callback a worker message goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlayerEvent {
+    Frame,
+    Stats,
+    Error,
+}
+
+// What the player does with one worker message: the status the
+// acknowledgement moves it to, and the callback to notify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Reaction {
+    status: Option<PlayerStatus>,
+    event: Option<PlayerEvent>,
+}
+
+fn react(status: PlayerStatus, message: &DecodeMessage) -> Reaction {
+    let to = |next: PlayerStatus| Reaction { status: Some(next), event: None };
+    let notify = |event: PlayerEvent| Reaction { status: None, event: Some(event) };
+
+    if status == PlayerStatus::Destroyed {
+        return Reaction { status: None, event: None };
+    }
+    match message {
+        DecodeMessage::DecoderReady => to(PlayerStatus::Ready),
+        DecodeMessage::DecoderStarted => to(PlayerStatus::Playing),
+        DecodeMessage::DecoderPaused => to(PlayerStatus::Pause),
+        DecodeMessage::DecoderClosed => to(PlayerStatus::Closed),
+        // The worker is only back to Idle once it says so
+        DecodeMessage::DecoderResetDone => to(PlayerStatus::Idle),
+        DecodeMessage::DecodedVideoFrame(_) => notify(PlayerEvent::Frame),
+        DecodeMessage::DecoderStats(_) => notify(PlayerEvent::Stats),
+        DecodeMessage::DecoderOpenError(_) => Reaction { status: Some(PlayerStatus::Error), event: Some(PlayerEvent::Error) },
+        DecodeMessage::DecoderError(_) => notify(PlayerEvent::Error),
+        _ => Reaction { status: None, event: None },
+    }
+}
+
+#[derive(Default)]
+struct PlayerCallbacks {
+    frame: Option<js_sys::Function>,
+    status: Option<js_sys::Function>,
+    stats: Option<js_sys::Function>,
+    error: Option<js_sys::Function>,
+}
+
+// Shared between Player and its onmessage closure
+struct PlayerShared {
+    status: PlayerStatus,
+    callbacks: PlayerCallbacks,
 }
 
 #[wasm_bindgen]
 pub struct Player {
-    status: PlayerStatus,
+    shared: Rc<RefCell<PlayerShared>>,
     worker: Option<Worker>,
-    width: u32,
-    height: u32,
+    on_message: Option<Closure<dyn FnMut(MessageEvent)>>,
+    next_request: u32,
+    config: PlayerConfig,
 }
 
 #[wasm_bindgen]
 impl Player {
+    // Uses the default classic worker at `./decoder.worker.js`
     #[wasm_bindgen(constructor)]
     pub fn new(width: u32, height: u32) -> Result<Player, JsValue> {
-        let worker = Worker::new("./decoder.worker.js")?;
-        
-        let player = Player {
-            status: PlayerStatus::Idle,
-            worker: Some(worker),
-            width,
-            height,
-        };
+        Self::create(PlayerConfig::new(width, height))
+    }
 
-        Ok(player)
+    #[wasm_bindgen]
+    pub fn with_config(config: JsValue) -> Result<Player, JsValue> {
+        Self::create(serde_wasm_bindgen::from_value(config)?)
     }
 
+    // Status only changes once the worker acknowledges a request
     #[wasm_bindgen]
     pub fn initialize(&mut self) -> Result<(), JsValue> {
-        if let Some(worker) = &self.worker {
-            let msg = JsValue::from_str("initialize");
-            worker.post_message(&msg)?;
-            self.status = PlayerStatus::Ready;
+        if self.status() != PlayerStatus::Idle {
+            return Err(JsValue::from_str("Player not idle; reset it first"));
         }
-        Ok(())
+        let options = DecoderOptions {
+            width: self.config.width,
+            height: self.config.height,
+            settings: self.config.settings.clone(),
+        };
+        self.send(DecodeMessage::DecoderInit(options))
     }
 
     #[wasm_bindgen]
     pub fn start(&mut self) -> Result<(), JsValue> {
-        if self.status != PlayerStatus::Ready {
+        if !matches!(self.status(), PlayerStatus::Ready | PlayerStatus::Pause) {
             return Err(JsValue::from_str("Player not ready"));
         }
-
-        if let Some(worker) = &self.worker {
-            let msg = JsValue::from_str("start");
-            worker.post_message(&msg)?;
-            self.status = PlayerStatus::Playing;
-        }
-        Ok(())
+        self.send(DecodeMessage::DecoderStart)
     }
 
     #[wasm_bindgen]
     pub fn pause(&mut self) -> Result<(), JsValue> {
-        if self.status != PlayerStatus::Playing {
+        if self.status() != PlayerStatus::Playing {
             return Err(JsValue::from_str("Player not playing"));
         }
+        self.send(DecodeMessage::DecoderPause)
+    }
+
+    // Releases the decoder but keeps the worker; reset() makes it usable again
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        if !matches!(self.status(), PlayerStatus::Ready | PlayerStatus::Playing | PlayerStatus::Pause | PlayerStatus::Error) {
+            return Err(JsValue::from_str("Player not open"));
+        }
+        self.send(DecodeMessage::DecoderClose)
+    }
+
+    // Returns the decoder to Idle from any status, e.g. after a failed open
+    #[wasm_bindgen]
+    pub fn reset(&mut self) -> Result<(), JsValue> {
+        self.send(DecodeMessage::DecoderReset)
+    }
+
+    // Sends token packets (`[{ frame_index, token, is_keyframe }]`) to decode.
+    // Float32Array tokens are transferred as they are, so they're detached
+    // afterwards; arrays of numbers are copied into one first.
+    #[wasm_bindgen]
+    pub fn decode(&mut self, tokens: JsValue) -> Result<(), JsValue> {
+        let (packets, transfer) = transferable_packets(&tokens)?;
+        let envelope = self.envelope(DecodeMessage::DecodeVideoBuffer(Vec::new()))?;
+        js_sys::Reflect::set(&js_sys::Reflect::get(&envelope, &"message".into())?, &"data".into(), &packets)?;
+        self.post(&envelope, Some(&transfer))
+    }
+
+    // The reply arrives through the stats callback
+    #[wasm_bindgen]
+    pub fn request_stats(&mut self) -> Result<(), JsValue> {
+        self.send(DecodeMessage::RequestStats)
+    }
+
+    // Terminates the worker and drops every callback. The player can't be
+    // used again afterwards.
+    #[wasm_bindgen]
+    pub fn destroy(&mut self) {
+        if self.status() == PlayerStatus::Destroyed {
+            return;
+        }
+        self.release();
 
-        if let Some(worker) = &self.worker {
-            let msg = JsValue::from_str("pause");
-            worker.post_message(&msg)?;
-            self.status = PlayerStatus::Pause;
+        let callback = {
+            let mut shared = self.shared.borrow_mut();
+            shared.status = PlayerStatus::Destroyed;
+            std::mem::take(&mut shared.callbacks).status
+        };
+        if let Some(callback) = callback {
+            call(&callback, &JsValue::from(PlayerStatus::Destroyed as u32));
         }
-        Ok(())
     }
-}
\ No newline at end of file
+
+    #[wasm_bindgen]
+    pub fn get_status(&self) -> PlayerStatus {
+        self.status()
+    }
+
+    // Called with each decoded frame: `{ frameIndex, timestamp, width, height, isKeyframe, format, data }`
+    #[wasm_bindgen]
+    pub fn set_frame_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.shared.borrow_mut().callbacks.frame = callback;
+    }
+
+    // Called with the new PlayerStatus after each acknowledged change
+    #[wasm_bindgen]
+    pub fn set_status_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.shared.borrow_mut().callbacks.status = callback;
+    }
+
+    #[wasm_bindgen]
+    pub fn set_stats_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.shared.borrow_mut().callbacks.stats = callback;
+    }
+
+    // Called with `{ kind, message, transition? }`
+    #[wasm_bindgen]
+    pub fn set_error_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.shared.borrow_mut().callbacks.error = callback;
+    }
+}
+
+impl Player {
+    fn create(config: PlayerConfig) -> Result<Player, JsValue> {
+        let worker = config.spawn_worker()?;
+        let shared = Rc::new(RefCell::new(PlayerShared {
+            status: PlayerStatus::Idle,
+            callbacks: PlayerCallbacks::default(),
+        }));
+
+        let handler_state = shared.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            handle_message(&handler_state, event.data());
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Player {
+            shared,
+            worker: Some(worker),
+            on_message: Some(on_message),
+            next_request: 0,
+            config,
+        })
+    }
+
+    fn status(&self) -> PlayerStatus {
+        self.shared.borrow().status
+    }
+
+    fn send(&mut self, message: DecodeMessage) -> Result<(), JsValue> {
+        let envelope = self.envelope(message)?;
+        self.post(&envelope, None)
+    }
+
+    // `message` in an envelope with the next request id
+    fn envelope(&mut self, message: DecodeMessage) -> Result<JsValue, JsValue> {
+        self.next_request = self.next_request.wrapping_add(1);
+        Ok(serde_wasm_bindgen::to_value(&WorkerMessage::reply(Some(self.next_request), message))?)
+    }
+
+    fn post(&self, envelope: &JsValue, transfer: Option<&js_sys::Array>) -> Result<(), JsValue> {
+        let worker = self.worker.as_ref().ok_or_else(|| JsValue::from_str("Player destroyed"))?;
+        match transfer {
+            Some(transfer) => worker.post_message_with_transfer(envelope, transfer),
+            None => worker.post_message(envelope),
+        }
+    }
+
+    // The handler has to be detached before its closure is freed
+    fn release(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            worker.set_onmessage(None);
+            worker.terminate();
+        }
+        self.on_message.take();
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+// Parses a message from the worker, applies its status change and hands the
+// payload to the matching callback. Callbacks run after the borrow is released
+// so they can call back into the player.
+fn handle_message(shared: &Rc<RefCell<PlayerShared>>, data: JsValue) {
+    let message = match serde_wasm_bindgen::from_value::<WorkerMessage>(data.clone()) {
+        Ok(envelope) => match envelope.check_version() {
+            Ok(()) => envelope.message,
+            Err(error) => DecodeMessage::DecoderError(error),
+        },
+        Err(e) => DecodeMessage::DecoderError(ErrorDetails::new(
+            ErrorKind::Protocol,
+            format!("Malformed worker message: {}", e),
+        )),
+    };
+
+    let (status_change, callback) = {
+        let mut shared = shared.borrow_mut();
+        let reaction = react(shared.status, &message);
+
+        let status_change = reaction.status
+            .filter(|&status| status != shared.status)
+            .map(|status| {
+                shared.status = status;
+                (status, shared.callbacks.status.clone())
+            });
+        let callback = reaction.event.and_then(|event| match event {
+            PlayerEvent::Frame => shared.callbacks.frame.clone(),
+            PlayerEvent::Stats => shared.callbacks.stats.clone(),
+            PlayerEvent::Error => shared.callbacks.error.clone(),
+        });
+        (status_change, callback)
+    };
+
+    if let Some((status, Some(callback))) = status_change {
+        call(&callback, &JsValue::from(status as u32));
+    }
+    if let Some(callback) = callback {
+        // Hand over the worker's own payload object, so frame pixels stay the
+        // transferred Uint8Array; errors raised here have no such object
+        let payload = js_sys::Reflect::get(&data, &"message".into())
+            .and_then(|message| js_sys::Reflect::get(&message, &"data".into()))
+            .ok()
+            .filter(|payload| !payload.is_undefined());
+        let payload = match (payload, &message) {
+            (Some(payload), DecodeMessage::DecodedVideoFrame(_) | DecodeMessage::DecoderStats(_)) => payload,
+            (_, DecodeMessage::DecoderError(error) | DecodeMessage::DecoderOpenError(error)) => {
+                serde_wasm_bindgen::to_value(error).unwrap_or(JsValue::NULL)
+            }
+            (payload, _) => payload.unwrap_or(JsValue::NULL),
+        };
+        call(&callback, &payload);
+    }
+}
+
+// Packets for DecodeVideoBuffer around the caller's own token arrays, and
+// their buffers to transfer, so tokens reach the worker without a copy like
+// frame pixels do on the way back. The worker checks the rest of each packet.
+fn transferable_packets(tokens: &JsValue) -> Result<(js_sys::Array, js_sys::Array), JsValue> {
+    if !js_sys::Array::is_array(tokens) {
+        return Err(JsValue::from_str("Expected an array of token packets"));
+    }
+
+    let packets = js_sys::Array::new();
+    let transfer = js_sys::Array::new();
+    for packet in js_sys::Array::from(tokens).iter() {
+        let values = match js_sys::Reflect::get(&packet, &"token".into())?.dyn_into::<js_sys::Float32Array>() {
+            Ok(values) => values,
+            Err(token) if js_sys::Array::is_array(&token) => js_sys::Float32Array::new(&token),
+            Err(_) => return Err(JsValue::from_str("Token must be a Float32Array or an array of numbers")),
+        };
+        // Tokens may be views into one shared buffer, which is listed once
+        let buffer = values.buffer();
+        if !transfer.includes(&buffer, 0) {
+            transfer.push(&buffer);
+        }
+
+        let copy = js_sys::Object::new();
+        for key in ["frame_index", "is_keyframe"] {
+            let value = js_sys::Reflect::get(&packet, &key.into())?;
+            if !value.is_undefined() {
+                js_sys::Reflect::set(&copy, &key.into(), &value)?;
+            }
+        }
+        js_sys::Reflect::set(&copy, &"token".into(), &values)?;
+        packets.push(&copy);
+    }
+    Ok((packets, transfer))
+}
+
+fn call(callback: &js_sys::Function, arg: &JsValue) {
+    if let Err(e) = callback.call1(&JsValue::NULL, arg) {
+        warn!("Player callback threw: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{FramePayload, Frame};
+
+    #[test]
+    fn test_status_follows_acknowledgements() {
+        let mut status = PlayerStatus::Idle;
+        for (message, expected) in [
+            (DecodeMessage::DecoderInited, PlayerStatus::Idle),
+            (DecodeMessage::DecoderReady, PlayerStatus::Ready),
+            (DecodeMessage::DecoderStarted, PlayerStatus::Playing),
+            (DecodeMessage::DecoderPaused, PlayerStatus::Pause),
+            (DecodeMessage::DecoderStarted, PlayerStatus::Playing),
+            (DecodeMessage::DecoderClosed, PlayerStatus::Closed),
+            (DecodeMessage::DecoderResetDone, PlayerStatus::Idle),
+        ] {
+            if let Some(next) = react(status, &message).status {
+                status = next;
+            }
+            assert_eq!(status, expected, "after {:?}", message);
+        }
+    }
+
+    #[test]
+    fn test_events_route_to_callbacks() {
+        let frame = DecodeMessage::DecodedVideoFrame(FramePayload::new(0, Frame::new(1, 1)));
+        assert_eq!(react(PlayerStatus::Playing, &frame), Reaction { status: None, event: Some(PlayerEvent::Frame) });
+
+        let error = ErrorDetails::new(ErrorKind::Decode, "bad token");
+        assert_eq!(react(PlayerStatus::Playing, &DecodeMessage::DecoderError(error.clone())).event, Some(PlayerEvent::Error));
+
+        // A failed open leaves the decoder in Error, so the player can't
+        // report Idle until a reset is acknowledged
+        let failed = react(PlayerStatus::Idle, &DecodeMessage::DecoderOpenError(error));
+        assert_eq!(failed, Reaction { status: Some(PlayerStatus::Error), event: Some(PlayerEvent::Error) });
+        assert_eq!(react(PlayerStatus::Error, &DecodeMessage::DecoderResetDone).status, Some(PlayerStatus::Idle));
+
+        // Nothing reaches a destroyed player
+        assert_eq!(react(PlayerStatus::Destroyed, &frame), Reaction { status: None, event: None });
+        assert_eq!(react(PlayerStatus::Destroyed, &DecodeMessage::DecoderReady).status, None);
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: PlayerConfig = serde_json::from_str(r#"{"width":640,"height":360}"#).unwrap();
+        assert_eq!(config, PlayerConfig::new(640, 360));
+        assert_eq!(config.worker_url, "./decoder.worker.js");
+        assert!(!config.module);
+
+        let config: PlayerConfig = serde_json::from_str(
+            r#"{"width":640,"height":360,"workerUrl":"/js/worker.mjs","module":true,"settings":{"targetFps":25}}"#,
+        ).unwrap();
+        assert_eq!(config.worker_url, "/js/worker.mjs");
+        assert!(config.module);
+        assert_eq!(config.settings.target_fps, Some(25));
+        assert!(!config.settings.adaptive_fps);
+    }
+}
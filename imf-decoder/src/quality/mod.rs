@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use crate::decoder::Frame;
+
+pub mod psnr;
+pub mod rd;
+pub mod ssim;
+pub mod temporal;
+
+pub use psnr::{psnr_rgb, psnr_y};
+pub use rd::{bd_rate, rd_sweep, RdInput, RdMetric, RdPoint};
+pub use ssim::{ms_ssim, ssim};
+pub use temporal::{temporal_consistency, TemporalFrame, TemporalReport, TemporalSummary, TemporalTracker};
+
+// PSNR reported for identical frames, in dB
+pub const MAX_PSNR: f64 = 100.0;
+
+// Full-reference quality of one decoded frame against its ground truth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameQuality {
+    pub psnr_y: f64,
+    pub psnr_rgb: f64,
+    pub ssim: f64,
+    pub ms_ssim: f64,
+}
+
+pub fn compare_frames(decoded: &Frame, reference: &Frame) -> Result<FrameQuality, String> {
+    Ok(FrameQuality {
+        psnr_y: psnr_y(decoded, reference)?,
+        psnr_rgb: psnr_rgb(decoded, reference)?,
+        ssim: ssim(decoded, reference)?,
+        ms_ssim: ms_ssim(decoded, reference)?,
+    })
+}
+
+// Per-sequence summary: means over frames plus the worst frame for each metric
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SequenceQuality {
+    pub frames: usize,
+    pub mean_psnr_y: f64,
+    pub mean_psnr_rgb: f64,
+    pub mean_ssim: f64,
+    pub mean_ms_ssim: f64,
+    pub min_psnr_y: f64,
+    pub min_ssim: f64,
+    pub min_ms_ssim: f64,
+}
+
+impl SequenceQuality {
+    pub fn aggregate<'a>(frames: impl IntoIterator<Item = &'a FrameQuality>) -> Self {
+        let mut summary = Self {
+            min_psnr_y: f64::INFINITY,
+            min_ssim: f64::INFINITY,
+            min_ms_ssim: f64::INFINITY,
+            ..Self::default()
+        };
+
+        for quality in frames {
+            summary.frames += 1;
+            summary.mean_psnr_y += quality.psnr_y;
+            summary.mean_psnr_rgb += quality.psnr_rgb;
+            summary.mean_ssim += quality.ssim;
+            summary.mean_ms_ssim += quality.ms_ssim;
+            summary.min_psnr_y = summary.min_psnr_y.min(quality.psnr_y);
+            summary.min_ssim = summary.min_ssim.min(quality.ssim);
+            summary.min_ms_ssim = summary.min_ms_ssim.min(quality.ms_ssim);
+        }
+
+        if summary.frames == 0 {
+            return Self::default();
+        }
+        let count = summary.frames as f64;
+        summary.mean_psnr_y /= count;
+        summary.mean_psnr_rgb /= count;
+        summary.mean_ssim /= count;
+        summary.mean_ms_ssim /= count;
+        summary
+    }
+}
+
+// Quality of each frame index measured so far. Playback can revisit a frame
+// (looping, seeking); the first measurement is kept so each index counts once.
+#[derive(Debug, Default)]
+pub struct QualityLog {
+    frames: BTreeMap<usize, FrameQuality>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IndexedQuality {
+    pub frame: usize,
+    pub quality: FrameQuality,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub frames: Vec<IndexedQuality>,
+    pub sequence: SequenceQuality,
+}
+
+impl QualityLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, index: usize, quality: FrameQuality) {
+        self.frames.entry(index).or_insert(quality);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.frames.contains_key(&index)
+    }
+
+    pub fn summary(&self) -> SequenceQuality {
+        SequenceQuality::aggregate(self.frames.values())
+    }
+
+    // Frames in index order plus the sequence summary
+    pub fn report(&self) -> QualityReport {
+        QualityReport {
+            frames: self.frames
+                .iter()
+                .map(|(&frame, &quality)| IndexedQuality { frame, quality })
+                .collect(),
+            sequence: self.summary(),
+        }
+    }
+}
+
+// Single-channel image in 0..=255 floats
+pub(crate) struct Plane {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f64>,
+}
+
+// BT.601 luma, converting YUV frames to RGBA first
+pub(crate) fn luma(frame: &Frame) -> Result<Plane, String> {
+    let rgba = frame.as_rgba()?;
+    Ok(Plane {
+        width: rgba.width,
+        height: rgba.height,
+        data: rgba.data
+            .chunks_exact(4)
+            .map(|pixel| 0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64)
+            .collect(),
+    })
+}
+
+pub(crate) fn check_dimensions(decoded: &Frame, reference: &Frame) -> Result<(), String> {
+    if (decoded.width, decoded.height) != (reference.width, reference.height) {
+        return Err(format!(
+            "Frame sizes differ: decoded {}x{}, reference {}x{}",
+            decoded.width, decoded.height, reference.width, reference.height
+        ));
+    }
+    if decoded.width == 0 || decoded.height == 0 {
+        return Err("Frames are empty".to_string());
+    }
+    if !decoded.is_valid() || !reference.is_valid() {
+        return Err("Frame data doesn't match its size and format".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_aggregation() {
+        let frames = [
+            FrameQuality { psnr_y: 30.0, psnr_rgb: 29.0, ssim: 0.9, ms_ssim: 0.95 },
+            FrameQuality { psnr_y: 40.0, psnr_rgb: 39.0, ssim: 0.8, ms_ssim: 0.97 },
+        ];
+
+        let summary = SequenceQuality::aggregate(&frames);
+        assert_eq!(summary.frames, 2);
+        assert_eq!(summary.mean_psnr_y, 35.0);
+        assert_eq!(summary.mean_psnr_rgb, 34.0);
+        assert!((summary.mean_ssim - 0.85).abs() < 1e-12);
+        assert_eq!(summary.min_psnr_y, 30.0);
+        assert_eq!(summary.min_ssim, 0.8);
+        assert_eq!(summary.min_ms_ssim, 0.95);
+
+        assert_eq!(SequenceQuality::aggregate(&[]), SequenceQuality::default());
+    }
+
+    #[test]
+    fn test_log_counts_each_frame_once() {
+        let good = FrameQuality { psnr_y: 40.0, psnr_rgb: 40.0, ssim: 0.99, ms_ssim: 0.99 };
+        let bad = FrameQuality { psnr_y: 20.0, psnr_rgb: 20.0, ssim: 0.5, ms_ssim: 0.6 };
+
+        let mut log = QualityLog::new();
+        log.record(3, good);
+        log.record(1, bad);
+        log.record(3, bad);
+
+        let report = log.report();
+        assert_eq!(report.frames.iter().map(|entry| entry.frame).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(report.frames[1].quality, good);
+        assert_eq!(report.sequence.frames, 2);
+        assert_eq!(report.sequence.mean_psnr_y, 30.0);
+    }
+
+    #[test]
+    fn test_compare_frames() {
+        let frame = Frame::new(16, 16);
+        let quality = compare_frames(&frame, &frame).unwrap();
+        assert_eq!(quality.psnr_y, MAX_PSNR);
+        assert!((quality.ssim - 1.0).abs() < 1e-9);
+
+        assert!(compare_frames(&frame, &Frame::new(16, 8)).is_err());
+        assert!(compare_frames(&Frame::new(0, 0), &Frame::new(0, 0)).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::decoder::Frame;
+use super::{check_dimensions, luma, MAX_PSNR};
+
+// PSNR of the BT.601 luma planes
+pub fn psnr_y(decoded: &Frame, reference: &Frame) -> Result<f64, String> {
+    check_dimensions(decoded, reference)?;
+    Ok(psnr_from_mse(mse(&luma(decoded)?.data, &luma(reference)?.data)))
+}
+
+// PSNR over the R, G and B channels together, ignoring alpha
+pub fn psnr_rgb(decoded: &Frame, reference: &Frame) -> Result<f64, String> {
+    check_dimensions(decoded, reference)?;
+    let (a, b) = (decoded.as_rgba()?, reference.as_rgba()?);
+
+    let rgb = |frame: &Frame| -> Vec<f64> {
+        frame.data.chunks_exact(4).flat_map(|pixel| pixel[..3].iter().map(|&v| v as f64)).collect()
+    };
+    Ok(psnr_from_mse(mse(&rgb(&a), &rgb(&b))))
+}
+
+fn mse(a: &[f64], b: &[f64]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f64>() / a.len() as f64
+}
+
+// Identical frames have infinite PSNR; clamp so sequence averages stay finite
+fn psnr_from_mse(mse: f64) -> f64 {
+    if mse == 0.0 {
+        return MAX_PSNR;
+    }
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: usize, height: usize, value: u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.set_data([value, value, value, 255].repeat(width * height));
+        frame
+    }
+
+    #[test]
+    fn test_psnr_values() {
+        let a = gray(4, 4, 100);
+        assert_eq!(psnr_y(&a, &a).unwrap(), MAX_PSNR);
+
+        // Uniform error of 1 gives 10 * log10(255^2) dB
+        let b = gray(4, 4, 101);
+        let expected = 20.0 * 255f64.log10();
+        assert!((psnr_rgb(&a, &b).unwrap() - expected).abs() < 1e-9);
+        assert!((psnr_y(&a, &b).unwrap() - expected).abs() < 1e-6);
+
+        assert!(psnr_y(&a, &gray(2, 4, 100)).is_err());
+    }
+}
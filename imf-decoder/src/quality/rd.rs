@@ -0,0 +1,303 @@
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::decoder::{decode_token, quantize_token, quantized_token_bits, Frame};
+use super::{compare_frames, SequenceQuality};
+
+// What an RD sweep runs over: one token per ground-truth frame, plus the size
+// of the reference payload sent once at the start
+pub struct RdInput {
+    pub width: usize,
+    pub height: usize,
+    pub fps: f64,
+    pub reference_bits: usize,
+    pub tokens: Vec<Vec<f32>>,
+    pub ground_truth: Vec<Frame>,
+}
+
+// One quantization setting of a sweep
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RdPoint {
+    pub bits: u8,
+    // Tokens plus the reference, spread over the sequence duration
+    pub kbps: f64,
+    // Token bits only
+    pub bits_per_frame: f64,
+    pub psnr_y: f64,
+    pub psnr_rgb: f64,
+    pub ssim: f64,
+    pub ms_ssim: f64,
+}
+
+// Quality axis used for BD-rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RdMetric {
+    #[default]
+    PsnrY,
+    Ssim,
+    MsSsim,
+}
+
+impl RdMetric {
+    fn of(&self, point: &RdPoint) -> f64 {
+        match self {
+            RdMetric::PsnrY => point.psnr_y,
+            RdMetric::Ssim => point.ssim,
+            RdMetric::MsSsim => point.ms_ssim,
+        }
+    }
+}
+
+impl FromStr for RdMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "psnr_y" | "psnr" => Ok(RdMetric::PsnrY),
+            "ssim" => Ok(RdMetric::Ssim),
+            "ms_ssim" => Ok(RdMetric::MsSsim),
+            _ => Err(format!("Unknown metric '{}', expected psnr_y, ssim or ms_ssim", s)),
+        }
+    }
+}
+
+const CSV_HEADER: [&str; 7] = ["bits", "kbps", "bits_per_frame", "psnr_y", "psnr_rgb", "ssim", "ms_ssim"];
+
+// Quantizes every token at each setting, decodes and measures it against the
+// ground truth
+pub fn rd_sweep(input: &RdInput, settings: &[u8]) -> Result<Vec<RdPoint>, String> {
+    if input.tokens.is_empty() || input.tokens.len() != input.ground_truth.len() {
+        return Err(format!(
+            "Need one token per ground-truth frame, got {} tokens and {} frames",
+            input.tokens.len(), input.ground_truth.len()
+        ));
+    }
+    if input.fps <= 0.0 {
+        return Err("Frame rate must be positive".to_string());
+    }
+
+    let frames = input.tokens.len() as f64;
+    let duration = frames / input.fps;
+    settings.iter().map(|&bits| {
+        let mut token_bits = 0;
+        let mut qualities = Vec::with_capacity(input.tokens.len());
+        for (token, truth) in input.tokens.iter().zip(&input.ground_truth) {
+            let quantized = quantize_token(token, bits)?;
+            let decoded = decode_token(&quantized, input.width, input.height)?;
+            qualities.push(compare_frames(&decoded, truth)?);
+            token_bits += quantized_token_bits(token.len(), bits);
+        }
+
+        let quality = SequenceQuality::aggregate(&qualities);
+        Ok(RdPoint {
+            bits,
+            kbps: (token_bits + input.reference_bits) as f64 / duration / 1000.0,
+            bits_per_frame: token_bits as f64 / frames,
+            psnr_y: quality.mean_psnr_y,
+            psnr_rgb: quality.mean_psnr_rgb,
+            ssim: quality.mean_ssim,
+            ms_ssim: quality.mean_ms_ssim,
+        })
+    }).collect()
+}
+
+pub fn to_csv(points: &[RdPoint]) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push('\n');
+    for p in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            p.bits, p.kbps, p.bits_per_frame, p.psnr_y, p.psnr_rgb, p.ssim, p.ms_ssim
+        ));
+    }
+    csv
+}
+
+pub fn to_json(points: &[RdPoint]) -> String {
+    let rows: Vec<String> = points.iter().map(|p| format!(
+        "  {{\"bits\": {}, \"kbps\": {}, \"bits_per_frame\": {}, \"psnr_y\": {}, \"psnr_rgb\": {}, \"ssim\": {}, \"ms_ssim\": {}}}",
+        p.bits, p.kbps, p.bits_per_frame, p.psnr_y, p.psnr_rgb, p.ssim, p.ms_ssim
+    )).collect();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+// Reads back what `to_csv` wrote; columns are matched by header name
+pub fn parse_csv(text: &str) -> Result<Vec<RdPoint>, String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next().ok_or("CSV is empty")?.split(',').map(str::trim).collect();
+    let columns = CSV_HEADER
+        .iter()
+        .map(|name| header.iter().position(|h| h == name).ok_or(format!("CSV has no '{}' column", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    lines.enumerate().map(|(row, line)| {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |i: usize| -> Result<f64, String> {
+            fields.get(columns[i])
+                .and_then(|value| value.parse().ok())
+                .ok_or(format!("Row {}: bad or missing '{}'", row + 1, CSV_HEADER[i]))
+        };
+        Ok(RdPoint {
+            bits: field(0)? as u8,
+            kbps: field(1)?,
+            bits_per_frame: field(2)?,
+            psnr_y: field(3)?,
+            psnr_rgb: field(4)?,
+            ssim: field(5)?,
+            ms_ssim: field(6)?,
+        })
+    }).collect()
+}
+
+// Bjøntegaard delta rate: average bitrate difference of `test` against
+// `anchor` at equal quality, in percent (negative means `test` is cheaper).
+// log10(rate) is fitted as a polynomial of quality (cubic with four or more
+// points) and integrated over the quality range both curves cover.
+pub fn bd_rate(anchor: &[RdPoint], test: &[RdPoint], metric: RdMetric) -> Result<f64, String> {
+    let anchor = RateCurve::fit(anchor, metric)?;
+    let test = RateCurve::fit(test, metric)?;
+
+    let low = anchor.low.max(test.low);
+    let high = anchor.high.min(test.high);
+    if high <= low {
+        return Err("The two runs' quality ranges don't overlap".to_string());
+    }
+
+    let difference = (test.integrate(low, high) - anchor.integrate(low, high)) / (high - low);
+    Ok((10f64.powf(difference) - 1.0) * 100.0)
+}
+
+// log10(rate) as a polynomial in normalized quality
+struct RateCurve {
+    coefficients: Vec<f64>,
+    center: f64,
+    scale: f64,
+    low: f64,
+    high: f64,
+}
+
+impl RateCurve {
+    fn fit(points: &[RdPoint], metric: RdMetric) -> Result<Self, String> {
+        if points.len() < 2 {
+            return Err("BD-rate needs at least two points per run".to_string());
+        }
+        if points.iter().any(|p| p.kbps <= 0.0) {
+            return Err("BD-rate needs positive bitrates".to_string());
+        }
+
+        let quality: Vec<f64> = points.iter().map(|p| metric.of(p)).collect();
+        let low = quality.iter().cloned().fold(f64::INFINITY, f64::min);
+        let high = quality.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if high <= low {
+            return Err("All points of a run have the same quality".to_string());
+        }
+
+        let center = (low + high) / 2.0;
+        let scale = (high - low) / 2.0;
+        let x: Vec<f64> = quality.iter().map(|q| (q - center) / scale).collect();
+        let y: Vec<f64> = points.iter().map(|p| p.kbps.log10()).collect();
+        let degree = (points.len() - 1).min(3);
+
+        Ok(Self { coefficients: least_squares(&x, &y, degree)?, center, scale, low, high })
+    }
+
+    fn at(&self, quality: f64) -> f64 {
+        let x = (quality - self.center) / self.scale;
+        self.coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+    }
+
+    // Simpson's rule; the integrand is a cubic at most, so this is exact
+    fn integrate(&self, low: f64, high: f64) -> f64 {
+        let mid = (low + high) / 2.0;
+        (high - low) / 6.0 * (self.at(low) + 4.0 * self.at(mid) + self.at(high))
+    }
+}
+
+// Polynomial coefficients (lowest power first) via the normal equations
+fn least_squares(x: &[f64], y: &[f64], degree: usize) -> Result<Vec<f64>, String> {
+    let n = degree + 1;
+    let mut matrix = vec![vec![0.0; n + 1]; n];
+    for (&xi, &yi) in x.iter().zip(y) {
+        for (row, line) in matrix.iter_mut().enumerate() {
+            for (col, cell) in line[..n].iter_mut().enumerate() {
+                *cell += xi.powi((row + col) as i32);
+            }
+            line[n] += yi * xi.powi(row as i32);
+        }
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))
+            .unwrap();
+        if matrix[pivot][col].abs() < 1e-12 {
+            return Err("Can't fit a rate curve through these points".to_string());
+        }
+        matrix.swap(col, pivot);
+        let pivot_row = matrix[col].clone();
+        for (row, line) in matrix.iter_mut().enumerate() {
+            if row != col {
+                let factor = line[col] / pivot_row[col];
+                for (cell, value) in line[col..].iter_mut().zip(&pivot_row[col..]) {
+                    *cell -= factor * value;
+                }
+            }
+        }
+    }
+    Ok((0..n).map(|i| matrix[i][n] / matrix[i][i]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(kbps: f64, psnr_y: f64) -> RdPoint {
+        RdPoint { bits: 0, kbps, bits_per_frame: 0.0, psnr_y, psnr_rgb: psnr_y, ssim: 0.9, ms_ssim: 0.9 }
+    }
+
+    #[test]
+    fn test_sweep_trades_rate_for_quality() {
+        let (width, height) = (8, 8);
+        let token: Vec<f32> = (0..width * height * 4).map(|i| (i * 7 % 256) as f32).collect();
+        let truth = decode_token(&token, width, height).unwrap();
+        let input = RdInput {
+            width,
+            height,
+            fps: 10.0,
+            reference_bits: 0,
+            tokens: vec![token.clone(), token],
+            ground_truth: vec![truth.clone(), truth],
+        };
+
+        let points = rd_sweep(&input, &[2, 4, 8]).unwrap();
+        assert_eq!(points.len(), 3);
+        assert!(points[0].kbps < points[1].kbps && points[1].kbps < points[2].kbps);
+        assert!(points[0].psnr_y < points[1].psnr_y && points[1].psnr_y < points[2].psnr_y);
+        assert_eq!(points[2].bits_per_frame, (256 * 8 + 64) as f64);
+
+        assert!(rd_sweep(&RdInput { tokens: Vec::new(), ..input }, &[8]).is_err());
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let points = vec![point(100.0, 30.5), point(250.0, 36.25)];
+        assert_eq!(parse_csv(&to_csv(&points)).unwrap(), points);
+        assert!(parse_csv("bits,kbps\n1,2\n").is_err());
+        assert!(to_json(&points).contains("\"psnr_y\": 36.25"));
+    }
+
+    #[test]
+    fn test_bd_rate() {
+        let anchor: Vec<_> = [30.0, 33.0, 36.0, 39.0].iter().map(|&q| point(10f64.powf(q / 10.0), q)).collect();
+        let cheaper: Vec<_> = anchor.iter().map(|p| point(p.kbps * 0.8, p.psnr_y)).collect();
+
+        assert!(bd_rate(&anchor, &anchor, RdMetric::PsnrY).unwrap().abs() < 1e-9);
+        assert!((bd_rate(&anchor, &cheaper, RdMetric::PsnrY).unwrap() + 20.0).abs() < 1e-9);
+        assert!((bd_rate(&cheaper, &anchor, RdMetric::PsnrY).unwrap() - 25.0).abs() < 1e-9);
+
+        let disjoint: Vec<_> = anchor.iter().map(|p| point(p.kbps, p.psnr_y + 20.0)).collect();
+        assert!(bd_rate(&anchor, &disjoint, RdMetric::PsnrY).is_err());
+        assert!(bd_rate(&anchor[..1], &anchor, RdMetric::PsnrY).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::decoder::Frame;
+use super::{check_dimensions, luma, Plane};
+
+// Constants from Wang et al. for 8-bit data
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+const WINDOW: usize = 11;
+const SIGMA: f64 = 1.5;
+
+// Per-scale exponents from the MS-SSIM paper, finest scale first
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+// Mean SSIM of the luma planes over an 11x11 Gaussian window (sigma 1.5).
+// Frames smaller than the window use a window as large as the frame.
+pub fn ssim(decoded: &Frame, reference: &Frame) -> Result<f64, String> {
+    check_dimensions(decoded, reference)?;
+    Ok(ssim_components(&luma(decoded)?, &luma(reference)?).0)
+}
+
+// Multi-scale SSIM over up to five dyadic scales. Scales whose frame would be
+// smaller than the window are dropped and the remaining weights renormalized.
+pub fn ms_ssim(decoded: &Frame, reference: &Frame) -> Result<f64, String> {
+    check_dimensions(decoded, reference)?;
+    let (mut a, mut b) = (luma(decoded)?, luma(reference)?);
+
+    let mut levels = 1;
+    let mut smallest = a.width.min(a.height);
+    while levels < MS_SSIM_WEIGHTS.len() && smallest / 2 >= WINDOW {
+        smallest /= 2;
+        levels += 1;
+    }
+    let weights = &MS_SSIM_WEIGHTS[..levels];
+    let total: f64 = weights.iter().sum();
+
+    let mut score = 1.0;
+    for (level, weight) in weights.iter().enumerate() {
+        let (ssim, cs) = ssim_components(&a, &b);
+        // Negative values have no meaningful fractional power
+        let value = if level + 1 == levels { ssim } else { cs };
+        score *= value.max(0.0).powf(weight / total);
+
+        if level + 1 < levels {
+            a = downsample(&a);
+            b = downsample(&b);
+        }
+    }
+    Ok(score)
+}
+
+// Mean SSIM and mean contrast-structure term over all valid window positions
+fn ssim_components(a: &Plane, b: &Plane) -> (f64, f64) {
+    let size = WINDOW.min(a.width).min(a.height);
+    let kernel = gaussian_kernel(size);
+
+    let product = |x: &Plane, y: &Plane| Plane {
+        width: x.width,
+        height: x.height,
+        data: x.data.iter().zip(&y.data).map(|(p, q)| p * q).collect(),
+    };
+
+    let mu_a = blur_valid(a, &kernel);
+    let mu_b = blur_valid(b, &kernel);
+    let aa = blur_valid(&product(a, a), &kernel);
+    let bb = blur_valid(&product(b, b), &kernel);
+    let ab = blur_valid(&product(a, b), &kernel);
+
+    let count = mu_a.data.len() as f64;
+    let (mut ssim_sum, mut cs_sum) = (0.0, 0.0);
+    for i in 0..mu_a.data.len() {
+        let (ma, mb) = (mu_a.data[i], mu_b.data[i]);
+        let var_a = aa.data[i] - ma * ma;
+        let var_b = bb.data[i] - mb * mb;
+        let covariance = ab.data[i] - ma * mb;
+
+        let cs = (2.0 * covariance + C2) / (var_a + var_b + C2);
+        let luminance = (2.0 * ma * mb + C1) / (ma * ma + mb * mb + C1);
+        ssim_sum += luminance * cs;
+        cs_sum += cs;
+    }
+    (ssim_sum / count, cs_sum / count)
+}
+
+fn gaussian_kernel(size: usize) -> Vec<f64> {
+    let center = (size as f64 - 1.0) / 2.0;
+    let kernel: Vec<f64> = (0..size)
+        .map(|i| (-(i as f64 - center).powi(2) / (2.0 * SIGMA * SIGMA)).exp())
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.into_iter().map(|k| k / sum).collect()
+}
+
+// Separable convolution keeping only positions where the kernel fits
+fn blur_valid(plane: &Plane, kernel: &[f64]) -> Plane {
+    let size = kernel.len();
+    let width = plane.width + 1 - size;
+    let height = plane.height + 1 - size;
+
+    let mut rows = vec![0.0; width * plane.height];
+    for y in 0..plane.height {
+        for x in 0..width {
+            let line = &plane.data[y * plane.width + x..][..size];
+            rows[y * width + x] = line.iter().zip(kernel).map(|(v, k)| v * k).sum();
+        }
+    }
+
+    let mut data = vec![0.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            data[y * width + x] = kernel.iter().enumerate().map(|(i, k)| rows[(y + i) * width + x] * k).sum();
+        }
+    }
+    Plane { width, height, data }
+}
+
+// 2x2 box filter, dropping an odd last row or column
+fn downsample(plane: &Plane) -> Plane {
+    let width = (plane.width / 2).max(1);
+    let height = (plane.height / 2).max(1);
+    let mut data = vec![0.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let sample = |dx: usize, dy: usize| {
+                let sx = (2 * x + dx).min(plane.width - 1);
+                let sy = (2 * y + dy).min(plane.height - 1);
+                plane.data[sy * plane.width + sx]
+            };
+            data[y * width + x] = (sample(0, 0) + sample(1, 0) + sample(0, 1) + sample(1, 1)) / 4.0;
+        }
+    }
+    Plane { width, height, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic texture so the structure term has something to compare
+    fn textured(width: usize, height: usize, noise: u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        for (i, pixel) in frame.data.chunks_mut(4).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let base = ((x * 37 + y * 91) % 200) as u8 + 20;
+            let jitter = if (x + y) % 2 == 0 { noise } else { 0 };
+            pixel.copy_from_slice(&[base.saturating_add(jitter), base, base, 255]);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_identical_frames_score_one() {
+        let frame = textured(64, 48, 0);
+        assert!((ssim(&frame, &frame).unwrap() - 1.0).abs() < 1e-9);
+        assert!((ms_ssim(&frame, &frame).unwrap() - 1.0).abs() < 1e-9);
+
+        // Smaller than the window
+        let tiny = textured(5, 3, 0);
+        assert!((ssim(&tiny, &tiny).unwrap() - 1.0).abs() < 1e-9);
+        assert!((ms_ssim(&tiny, &tiny).unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scores_fall_with_distortion() {
+        let reference = textured(96, 96, 0);
+        let slight = textured(96, 96, 10);
+        let heavy = textured(96, 96, 60);
+
+        let (slight_ssim, heavy_ssim) = (ssim(&slight, &reference).unwrap(), ssim(&heavy, &reference).unwrap());
+        assert!(slight_ssim < 1.0 && heavy_ssim < slight_ssim);
+
+        let (slight_ms, heavy_ms) = (ms_ssim(&slight, &reference).unwrap(), ms_ssim(&heavy, &reference).unwrap());
+        assert!(slight_ms < 1.0 && heavy_ms < slight_ms);
+        assert!(heavy_ms > 0.0);
+    }
+
+    #[test]
+    fn test_gaussian_kernel_is_normalized() {
+        let kernel = gaussian_kernel(WINDOW);
+        assert!((kernel.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert_eq!(kernel[0], kernel[WINDOW - 1]);
+        assert!(kernel[WINDOW / 2] > kernel[0]);
+    }
+}
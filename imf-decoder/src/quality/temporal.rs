@@ -0,0 +1,222 @@
+use serde::{Serialize, Deserialize};
+use crate::decoder::Frame;
+use super::{check_dimensions, luma, Plane};
+
+// Luma change (out of 255) below which a ground-truth pixel counts as still
+const STILL_THRESHOLD: f64 = 2.0;
+
+// Temporal consistency of one decoded frame against the previous one, judged
+// by how the ground truth changed over the same step. All values are luma
+// levels (0-255) except `stability`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TemporalFrame {
+    pub frame: usize,
+    // Mean |decoded delta - ground-truth delta|
+    pub delta_error: f64,
+    // Mean |decoded delta| minus mean |ground-truth delta|; positive means
+    // the decode changes more than the source did (flicker), negative that it
+    // lags behind (over-smoothing)
+    pub flicker: f64,
+    // Mean |decoded delta| over pixels the ground truth keeps still, i.e. the
+    // warping error with the identity warp those pixels call for
+    pub warp_error: f64,
+    // 1 / (1 + warp_error): 1 when still areas are perfectly stable
+    pub stability: f64,
+    // Fraction of pixels the ground truth keeps still
+    pub still_fraction: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemporalSummary {
+    pub frames: usize,
+    pub mean_delta_error: f64,
+    pub mean_flicker: f64,
+    pub mean_warp_error: f64,
+    pub mean_stability: f64,
+    pub worst_delta_error: f64,
+    pub worst_frame: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemporalReport {
+    pub frames: Vec<TemporalFrame>,
+    pub sequence: TemporalSummary,
+}
+
+impl TemporalSummary {
+    pub fn aggregate<'a>(frames: impl IntoIterator<Item = &'a TemporalFrame>) -> Self {
+        let mut summary = Self::default();
+        for frame in frames {
+            summary.frames += 1;
+            summary.mean_delta_error += frame.delta_error;
+            summary.mean_flicker += frame.flicker;
+            summary.mean_warp_error += frame.warp_error;
+            summary.mean_stability += frame.stability;
+            if summary.worst_frame.is_none() || frame.delta_error > summary.worst_delta_error {
+                summary.worst_delta_error = frame.delta_error;
+                summary.worst_frame = Some(frame.frame);
+            }
+        }
+
+        if summary.frames > 0 {
+            let count = summary.frames as f64;
+            summary.mean_delta_error /= count;
+            summary.mean_flicker /= count;
+            summary.mean_warp_error /= count;
+            summary.mean_stability /= count;
+        }
+        summary
+    }
+}
+
+// Feeds (decoded, ground truth) pairs in display order and scores each pair
+// against the one before it
+#[derive(Default)]
+pub struct TemporalTracker {
+    previous: Option<(Plane, Plane)>,
+    frames: Vec<TemporalFrame>,
+}
+
+impl TemporalTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the score for `frame` once there is a previous pair to compare with
+    pub fn push(&mut self, frame: usize, decoded: &Frame, ground_truth: &Frame) -> Result<Option<TemporalFrame>, String> {
+        check_dimensions(decoded, ground_truth)?;
+        let current = (luma(decoded)?, luma(ground_truth)?);
+
+        let score = match &self.previous {
+            Some((prev_decoded, prev_truth)) if prev_decoded.width == current.0.width && prev_decoded.height == current.0.height => {
+                Some(score_step(frame, prev_decoded, prev_truth, &current.0, &current.1))
+            }
+            _ => None,
+        };
+
+        self.previous = Some(current);
+        if let Some(score) = score {
+            self.frames.push(score);
+        }
+        Ok(score)
+    }
+
+    // Forgets the previous pair, e.g. after a seek or a frame that couldn't
+    // be decoded, so the next frame isn't compared across the gap
+    pub fn break_sequence(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn report(&self) -> TemporalReport {
+        TemporalReport {
+            frames: self.frames.clone(),
+            sequence: TemporalSummary::aggregate(&self.frames),
+        }
+    }
+}
+
+// Scores a whole sequence; frame numbers are positions in the slices
+pub fn temporal_consistency(decoded: &[Frame], ground_truth: &[Frame]) -> Result<TemporalReport, String> {
+    if decoded.len() != ground_truth.len() {
+        return Err(format!(
+            "Sequence lengths differ: decoded {}, ground truth {}",
+            decoded.len(), ground_truth.len()
+        ));
+    }
+
+    let mut tracker = TemporalTracker::new();
+    for (index, (decoded, truth)) in decoded.iter().zip(ground_truth).enumerate() {
+        tracker.push(index, decoded, truth)?;
+    }
+    Ok(tracker.report())
+}
+
+fn score_step(frame: usize, prev_decoded: &Plane, prev_truth: &Plane, decoded: &Plane, truth: &Plane) -> TemporalFrame {
+    let count = decoded.data.len() as f64;
+    let (mut delta_error, mut decoded_motion, mut truth_motion) = (0.0, 0.0, 0.0);
+    let (mut still, mut still_error) = (0usize, 0.0);
+
+    for i in 0..decoded.data.len() {
+        let decoded_delta = decoded.data[i] - prev_decoded.data[i];
+        let truth_delta = truth.data[i] - prev_truth.data[i];
+
+        delta_error += (decoded_delta - truth_delta).abs();
+        decoded_motion += decoded_delta.abs();
+        truth_motion += truth_delta.abs();
+        if truth_delta.abs() < STILL_THRESHOLD {
+            still += 1;
+            still_error += decoded_delta.abs();
+        }
+    }
+
+    let warp_error = if still > 0 { still_error / still as f64 } else { 0.0 };
+    TemporalFrame {
+        frame,
+        delta_error: delta_error / count,
+        flicker: (decoded_motion - truth_motion) / count,
+        warp_error,
+        stability: 1.0 / (1.0 + warp_error),
+        still_fraction: still as f64 / count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Left half is a still gray, right half brightens by 4 levels a frame
+    fn scene(index: usize, shimmer: u8) -> Frame {
+        let mut frame = Frame::new(8, 4);
+        for (i, pixel) in frame.data.chunks_mut(4).enumerate() {
+            let still = i % 8 < 4;
+            let mut value = if still { 100 } else { 60 + 4 * index as u8 };
+            if still && index % 2 == 1 {
+                value += shimmer;
+            }
+            pixel.copy_from_slice(&[value, value, value, 255]);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_faithful_decode_is_stable() {
+        let truth: Vec<_> = (0..5).map(|i| scene(i, 0)).collect();
+        let report = temporal_consistency(&truth, &truth).unwrap();
+
+        assert_eq!(report.frames.len(), 4);
+        assert_eq!(report.frames[0].frame, 1);
+        assert_eq!(report.sequence.mean_delta_error, 0.0);
+        assert_eq!(report.sequence.mean_flicker, 0.0);
+        assert_eq!(report.sequence.mean_stability, 1.0);
+        assert_eq!(report.frames[0].still_fraction, 0.5);
+    }
+
+    #[test]
+    fn test_shimmer_in_still_areas_is_flagged() {
+        let truth: Vec<_> = (0..5).map(|i| scene(i, 0)).collect();
+        let decoded: Vec<_> = (0..5).map(|i| scene(i, 6)).collect();
+        let report = temporal_consistency(&decoded, &truth).unwrap();
+
+        // Still half toggles by 6 every frame
+        for frame in &report.frames {
+            assert!((frame.warp_error - 6.0).abs() < 1e-9);
+            assert!((frame.delta_error - 3.0).abs() < 1e-9);
+            assert!(frame.flicker > 0.0);
+        }
+        assert!((report.sequence.mean_stability - 1.0 / 7.0).abs() < 1e-9);
+        assert_eq!(report.sequence.worst_frame, Some(1));
+    }
+
+    #[test]
+    fn test_gaps_and_mismatches() {
+        let mut tracker = TemporalTracker::new();
+        assert_eq!(tracker.push(0, &scene(0, 0), &scene(0, 0)).unwrap(), None);
+        tracker.break_sequence();
+        assert_eq!(tracker.push(5, &scene(5, 0), &scene(5, 0)).unwrap(), None);
+        assert!(tracker.push(6, &scene(6, 0), &scene(6, 0)).unwrap().is_some());
+        assert_eq!(tracker.report().sequence.frames, 1);
+
+        assert!(tracker.push(7, &scene(7, 0), &Frame::new(2, 2)).is_err());
+        assert!(temporal_consistency(&[scene(0, 0)], &[]).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageBitmap, ImageData};
+use crate::decoder::Frame;
+use super::{js_error, Renderer, RendererKind};
+
+pub struct Canvas2dRenderer {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    overlay: Option<Scratch>, // Staging canvas for draw_overlay, created on first use
+}
+
+impl Canvas2dRenderer {
+    pub fn new(canvas: HtmlCanvasElement) -> Result<Self, String> {
+        let context = context_2d(&canvas)?;
+        Ok(Self { canvas, context, overlay: None })
+    }
+}
+
+impl Renderer for Canvas2dRenderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::Canvas2d
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.canvas.width(), self.canvas.height())
+    }
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        let frame = frame.as_rgba()?;
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&frame.data),
+            frame.width as u32,
+            frame.height as u32
+        ).map_err(js_error)?;
+        self.context.put_image_data(&image_data, 0.0, 0.0).map_err(js_error)
+    }
+
+    fn present_bitmap(&mut self, bitmap: &ImageBitmap, alpha: f64) -> Result<(), String> {
+        self.context.set_global_alpha(alpha);
+        let result = self.context.draw_image_with_image_bitmap(bitmap, 0.0, 0.0);
+        self.context.set_global_alpha(1.0);
+        result.map_err(js_error)
+    }
+
+    // putImageData replaces pixels, so stage the overlay on another canvas
+    // and drawImage it to get blending
+    fn draw_overlay(&mut self, overlay: &Frame) -> Result<(), String> {
+        let overlay = overlay.as_rgba()?;
+        if self.overlay.is_none() {
+            self.overlay = Some(Scratch::new()?);
+        }
+        let scratch = self.overlay.as_ref().unwrap();
+
+        scratch.set_size(overlay.width as u32, overlay.height as u32);
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&overlay.data),
+            overlay.width as u32,
+            overlay.height as u32
+        ).map_err(js_error)?;
+        scratch.context.put_image_data(&image_data, 0.0, 0.0).map_err(js_error)?;
+
+        self.context
+            .draw_image_with_html_canvas_element(&scratch.canvas, 0.0, 0.0)
+            .map_err(js_error)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), String> {
+        let (width, height) = self.size();
+        self.context.clear_rect(0.0, 0.0, width as f64, height as f64);
+        Ok(())
+    }
+
+    fn capture(&self) -> Result<Frame, String> {
+        let (width, height) = self.size();
+        let image_data = self.context
+            .get_image_data(0.0, 0.0, width as f64, height as f64)
+            .map_err(js_error)?;
+
+        let mut frame = Frame::new(width as usize, height as usize);
+        frame.set_data(image_data.data().0);
+        Ok(frame)
+    }
+}
+
+// Reads ImageBitmap pixels back through a detached 2D canvas
+pub struct BitmapReader {
+    scratch: Scratch,
+}
+
+impl BitmapReader {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self { scratch: Scratch::new()? })
+    }
+
+    pub fn read(&mut self, bitmap: &ImageBitmap) -> Result<Frame, String> {
+        let (width, height) = (bitmap.width(), bitmap.height());
+        self.scratch.set_size(width, height);
+        self.scratch.context.draw_image_with_image_bitmap(bitmap, 0.0, 0.0).map_err(js_error)?;
+
+        let image_data = self.scratch.context
+            .get_image_data(0.0, 0.0, width as f64, height as f64)
+            .map_err(js_error)?;
+        let mut frame = Frame::new(width as usize, height as usize);
+        frame.set_data(image_data.data().0);
+        Ok(frame)
+    }
+}
+
+// Canvas that isn't attached to the page
+struct Scratch {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+
+impl Scratch {
+    fn new() -> Result<Self, String> {
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("No document to create a canvas in")?
+            .create_element("canvas")
+            .map_err(js_error)?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|e| js_error(e.into()))?;
+        let context = context_2d(&canvas)?;
+        Ok(Self { canvas, context })
+    }
+
+    // Resizes (which also clears) or clears the canvas
+    fn set_size(&self, width: u32, height: u32) {
+        if (self.canvas.width(), self.canvas.height()) != (width, height) {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        } else {
+            self.context.clear_rect(0.0, 0.0, width as f64, height as f64);
+        }
+    }
+}
+
+fn context_2d(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d, String> {
+    canvas
+        .get_context("2d")
+        .map_err(js_error)?
+        .ok_or("2D context not available")?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(|e| js_error(e.into()))
+}
@@ -0,0 +1,258 @@
+use serde::{Serialize, Deserialize};
+use crate::decoder::Frame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareLayout {
+    // Ground truth | reference | decoded, plus the heatmap when enabled
+    #[default]
+    SideBySide,
+    // Ground truth left of the split, decoded (or the heatmap) right of it
+    SplitWipe,
+}
+
+// How the comparison view composes the frames for one index
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CompareView {
+    #[serde(default)]
+    pub layout: CompareLayout,
+    // Split position for SplitWipe, as a fraction of the width
+    #[serde(default = "default_wipe")]
+    pub wipe: f32,
+    // Show |ground truth - decoded| as a heatmap
+    #[serde(default)]
+    pub heatmap: bool,
+    // Heatmap scale; a difference of 255 / gain saturates
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+fn default_wipe() -> f32 { 0.5 }
+fn default_gain() -> f32 { 4.0 }
+
+impl Default for CompareView {
+    fn default() -> Self {
+        Self {
+            layout: CompareLayout::default(),
+            wipe: default_wipe(),
+            heatmap: false,
+            gain: default_gain(),
+        }
+    }
+}
+
+// The frames shown for one index. Missing ones are drawn black.
+#[derive(Default)]
+pub struct ComparePanels<'a> {
+    pub ground_truth: Option<&'a Frame>,
+    pub reference: Option<&'a Frame>,
+    pub decoded: Option<&'a Frame>,
+}
+
+impl ComparePanels<'_> {
+    // Largest panel size; smaller panels sit at the top-left of their cell
+    fn cell_size(&self) -> (usize, usize) {
+        [self.ground_truth, self.reference, self.decoded]
+            .into_iter()
+            .flatten()
+            .fold((0, 0), |(w, h), frame| (w.max(frame.width), h.max(frame.height)))
+    }
+}
+
+impl CompareView {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.wipe) {
+            return Err(format!("wipe must be between 0 and 1, got {}", self.wipe));
+        }
+        if !(self.gain > 0.0 && self.gain <= 255.0) {
+            return Err(format!("gain must be between 0 and 255, got {}", self.gain));
+        }
+        Ok(())
+    }
+
+    pub fn compose(&self, panels: &ComparePanels) -> Result<Frame, String> {
+        let (width, height) = panels.cell_size();
+        let ground_truth = panels.ground_truth.map(Frame::as_rgba).transpose()?;
+        let reference = panels.reference.map(Frame::as_rgba).transpose()?;
+        let decoded = panels.decoded.map(Frame::as_rgba).transpose()?;
+
+        let heatmap = match (&ground_truth, &decoded) {
+            (Some(truth), Some(decoded)) if self.heatmap => Some(difference_heatmap(truth, decoded, self.gain)?),
+            _ => None,
+        };
+
+        match self.layout {
+            CompareLayout::SideBySide => {
+                let mut cells = vec![ground_truth.as_deref(), reference.as_deref(), decoded.as_deref()];
+                if self.heatmap {
+                    cells.push(heatmap.as_ref());
+                }
+
+                let mut out = blank(width * cells.len(), height);
+                for (column, cell) in cells.into_iter().enumerate() {
+                    if let Some(frame) = cell {
+                        blit(&mut out, frame, column * width, 0..width);
+                    }
+                }
+                Ok(out)
+            }
+            CompareLayout::SplitWipe => {
+                let split = (self.wipe * width as f32).round() as usize;
+                let right = if self.heatmap { heatmap.as_ref() } else { decoded.as_deref() };
+
+                let mut out = blank(width, height);
+                if let Some(frame) = ground_truth.as_deref() {
+                    blit(&mut out, frame, 0, 0..split);
+                }
+                if let Some(frame) = right {
+                    blit(&mut out, frame, 0, split..width);
+                }
+
+                // Divider on the first column of the right side
+                if split < width {
+                    for y in 0..height {
+                        let idx = (y * width + split) * 4;
+                        out.data[idx..idx + 4].copy_from_slice(&[255, 255, 255, 255]);
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+// Per-pixel mean absolute RGB difference, scaled by `gain` and mapped through
+// a black -> purple -> red -> yellow -> white ramp. Covers the overlap of the
+// two frames.
+pub fn difference_heatmap(a: &Frame, b: &Frame, gain: f32) -> Result<Frame, String> {
+    let a = a.as_rgba()?;
+    let b = b.as_rgba()?;
+    let width = a.width.min(b.width);
+    let height = a.height.min(b.height);
+
+    let mut out = blank(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let pa = &a.data[(y * a.width + x) * 4..][..3];
+            let pb = &b.data[(y * b.width + x) * 4..][..3];
+            let diff: u32 = pa.iter().zip(pb).map(|(&p, &q)| p.abs_diff(q) as u32).sum();
+
+            let t = (diff as f32 / (3.0 * 255.0) * gain).min(1.0);
+            let idx = (y * width + x) * 4;
+            out.data[idx..idx + 3].copy_from_slice(&heat_color(t));
+        }
+    }
+    Ok(out)
+}
+
+fn heat_color(t: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [128.0, 0.0, 160.0],
+        [230.0, 30.0, 30.0],
+        [255.0, 220.0, 0.0],
+        [255.0, 255.0, 255.0],
+    ];
+
+    let position = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (position.floor() as usize).min(STOPS.len() - 2);
+    let f = position - index as f32;
+    let (from, to) = (STOPS[index], STOPS[index + 1]);
+    [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * f).round() as u8)
+}
+
+fn blank(width: usize, height: usize) -> Frame {
+    let mut frame = Frame::new(width, height);
+    frame.data.chunks_mut(4).for_each(|pixel| pixel[3] = 255);
+    frame
+}
+
+// Copies `columns` of `src` (clipped to its width) into `dst` shifted right by `x_offset`
+fn blit(dst: &mut Frame, src: &Frame, x_offset: usize, columns: std::ops::Range<usize>) {
+    let end = columns.end.min(src.width);
+    if columns.start >= end {
+        return;
+    }
+
+    for y in 0..src.height.min(dst.height) {
+        let from = (y * src.width + columns.start) * 4;
+        let to = (y * dst.width + x_offset + columns.start) * 4;
+        let len = (end - columns.start) * 4;
+        dst.data[to..to + len].copy_from_slice(&src.data[from..from + len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: usize, height: usize, value: u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.set_data([value, value, value, 255].repeat(width * height));
+        frame
+    }
+
+    fn pixel(frame: &Frame, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * frame.width + x) * 4;
+        frame.data[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_side_by_side_keeps_panel_order() {
+        let (truth, reference, decoded) = (solid(2, 2, 10), solid(2, 2, 20), solid(2, 2, 30));
+        let panels = ComparePanels {
+            ground_truth: Some(&truth),
+            reference: Some(&reference),
+            decoded: Some(&decoded),
+        };
+
+        let view = CompareView { heatmap: true, ..CompareView::default() };
+        let out = view.compose(&panels).unwrap();
+        assert_eq!((out.width, out.height), (8, 2));
+        assert_eq!(pixel(&out, 1, 1), [10, 10, 10, 255]);
+        assert_eq!(pixel(&out, 2, 0), [20, 20, 20, 255]);
+        assert_eq!(pixel(&out, 5, 1), [30, 30, 30, 255]);
+        // 20 / 255 * 4 of the way up the ramp
+        assert_ne!(pixel(&out, 6, 0), [0, 0, 0, 255]);
+
+        // A missing reference leaves its cell black
+        let panels = ComparePanels { reference: None, ..panels };
+        let out = CompareView::default().compose(&panels).unwrap();
+        assert_eq!((out.width, out.height), (6, 2));
+        assert_eq!(pixel(&out, 3, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_split_wipe_divides_at_position() {
+        let (truth, decoded) = (solid(4, 1, 10), solid(4, 1, 30));
+        let panels = ComparePanels { ground_truth: Some(&truth), decoded: Some(&decoded), ..Default::default() };
+
+        let view = CompareView { layout: CompareLayout::SplitWipe, wipe: 0.25, ..CompareView::default() };
+        let out = view.compose(&panels).unwrap();
+        assert_eq!((out.width, out.height), (4, 1));
+        assert_eq!(pixel(&out, 0, 0), [10, 10, 10, 255]);
+        assert_eq!(pixel(&out, 1, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&out, 3, 0), [30, 30, 30, 255]);
+
+        let view = CompareView { wipe: 1.0, ..view };
+        assert_eq!(view.compose(&panels).unwrap().data, truth.data);
+    }
+
+    #[test]
+    fn test_heatmap_ramp() {
+        let base = solid(1, 1, 100);
+        assert_eq!(pixel(&difference_heatmap(&base, &base, 4.0).unwrap(), 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&difference_heatmap(&base, &solid(1, 1, 255), 4.0).unwrap(), 0, 0), [255, 255, 255, 255]);
+
+        let small = pixel(&difference_heatmap(&base, &solid(1, 1, 110), 4.0).unwrap(), 0, 0);
+        let large = pixel(&difference_heatmap(&base, &solid(1, 1, 140), 4.0).unwrap(), 0, 0);
+        assert!(large[0] > small[0]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CompareView::default().validate().is_ok());
+        assert!(CompareView { wipe: 1.5, ..CompareView::default() }.validate().is_err());
+        assert!(CompareView { gain: 0.0, ..CompareView::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use web_sys::ImageBitmap;
+use crate::decoder::Frame;
+use super::{Renderer, RendererKind};
+
+// Renders into memory. Behaves like putImageData on a canvas of the same
+// size: frames are drawn at the top-left and clipped, never scaled.
+pub struct HeadlessRenderer {
+    target: Frame,
+    presented: usize,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            target: Frame::new(width as usize, height as usize),
+            presented: 0,
+        }
+    }
+
+    // Frames presented since creation
+    pub fn presented(&self) -> usize {
+        self.presented
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.target.data
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::Headless
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.target.width as u32, self.target.height as u32)
+    }
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        let frame = frame.as_rgba()?;
+
+        let columns = frame.width.min(self.target.width) * 4;
+        let rows = frame.height.min(self.target.height);
+        for y in 0..rows {
+            let src = y * frame.width * 4;
+            let dst = y * self.target.width * 4;
+            self.target.data[dst..dst + columns].copy_from_slice(&frame.data[src..src + columns]);
+        }
+
+        self.target.frame_index = frame.frame_index;
+        self.target.timestamp = frame.timestamp;
+        self.presented += 1;
+        Ok(())
+    }
+
+    fn present_bitmap(&mut self, _bitmap: &ImageBitmap, _alpha: f64) -> Result<(), String> {
+        Err("Headless renderer can't read ImageBitmap pixels".to_string())
+    }
+
+    fn draw_overlay(&mut self, overlay: &Frame) -> Result<(), String> {
+        let overlay = overlay.as_rgba()?;
+        for y in 0..overlay.height.min(self.target.height) {
+            for x in 0..overlay.width.min(self.target.width) {
+                let src = &overlay.data[(y * overlay.width + x) * 4..][..4];
+                let alpha = src[3] as u32;
+                let dst = &mut self.target.data[(y * self.target.width + x) * 4..][..3];
+                for (d, &s) in dst.iter_mut().zip(src) {
+                    *d = ((s as u32 * alpha + *d as u32 * (255 - alpha) + 127) / 255) as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.target = Frame::new(width as usize, height as usize);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), String> {
+        self.target.data.fill(0);
+        Ok(())
+    }
+
+    fn capture(&self) -> Result<Frame, String> {
+        Ok(self.target.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::PixelFormat;
+
+    fn solid(width: usize, height: usize, value: u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.data.fill(value);
+        frame
+    }
+
+    #[test]
+    fn test_present_and_capture() {
+        let mut renderer = HeadlessRenderer::new(2, 2);
+        renderer.present(&solid(2, 2, 200)).unwrap();
+
+        let captured = renderer.capture().unwrap();
+        assert_eq!(captured.data, vec![200; 16]);
+        assert_eq!(renderer.presented(), 1);
+
+        renderer.clear().unwrap();
+        assert!(renderer.pixels().iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn test_mismatched_frames_are_clipped() {
+        let mut renderer = HeadlessRenderer::new(3, 2);
+
+        // Smaller frame only covers the top-left corner
+        renderer.present(&solid(1, 1, 9)).unwrap();
+        assert_eq!(&renderer.pixels()[..4], &[9; 4]);
+        assert_eq!(&renderer.pixels()[4..], &[0; 20]);
+
+        // Larger frame is cut at the right and bottom edges
+        renderer.present(&solid(4, 4, 7)).unwrap();
+        assert!(renderer.pixels().iter().all(|&value| value == 7));
+    }
+
+    #[test]
+    fn test_overlay_blends_by_alpha() {
+        let mut renderer = HeadlessRenderer::new(2, 1);
+        renderer.present(&solid(2, 1, 100)).unwrap();
+
+        let mut overlay = Frame::new(2, 1);
+        overlay.set_data(vec![255, 255, 255, 0, 200, 0, 0, 255]);
+        renderer.draw_overlay(&overlay).unwrap();
+
+        // Transparent pixels leave the image alone, opaque ones replace it
+        assert_eq!(renderer.pixels(), &[100, 100, 100, 100, 200, 0, 0, 100]);
+        assert_eq!(renderer.presented(), 1);
+    }
+
+    #[test]
+    fn test_yuv_frames_are_converted() {
+        let mut renderer = HeadlessRenderer::new(2, 2);
+        let mut frame = Frame::with_format(2, 2, PixelFormat::I420);
+        frame.set_data(vec![235, 235, 235, 235, 128, 128]);
+
+        renderer.present(&frame).unwrap();
+        assert!(renderer.pixels().iter().all(|&value| value == 255));
+    }
+
+    #[test]
+    fn test_resize_and_invalid_frame() {
+        let mut renderer = HeadlessRenderer::new(2, 2);
+        renderer.present(&solid(2, 2, 1)).unwrap();
+        renderer.resize(4, 1).unwrap();
+        assert_eq!(renderer.size(), (4, 1));
+        assert_eq!(renderer.pixels(), &[0; 16]);
+
+        let mut broken = Frame::new(2, 2);
+        broken.data.truncate(3);
+        assert!(renderer.present(&broken).is_err());
+        assert!(renderer.draw_overlay(&broken).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::decoder::{Frame, PresentationStats, QueueStats};
+use crate::utils::Metrics;
+
+// Everything the diagnostic overlay shows for one presented frame
+#[derive(Debug, Clone, Default)]
+pub struct HudStats {
+    pub frame_index: usize,
+    pub pts: f64,                 // Presentation timestamp in ms
+    pub instant_fps: f64,         // From the last frame time
+    pub average_fps: f64,         // Over the metrics window
+    pub decode_time: f64,         // Last queue processing time in ms
+    pub queue_depths: [usize; 3], // Input, processing, output
+    pub frames_dropped: usize,    // Dropped by the queue or presented too late
+    pub frames_repeated: usize,   // Redrawn because the next frame wasn't ready
+    pub packet_bits: Option<usize>, // Size of the token shown, when decoding tokens
+    pub frame_times: Vec<f64>,    // Rolling window for the graph, oldest first
+    pub frame_budget: f64,        // Target frame interval in ms
+}
+
+impl HudStats {
+    pub fn collect(metrics: &Metrics, queue: &QueueStats, presentation: &PresentationStats) -> Self {
+        Self {
+            instant_fps: metrics.last_frame_time().filter(|&time| time > 0.0).map_or(0.0, |time| 1000.0 / time),
+            average_fps: metrics.fps(),
+            decode_time: queue.last_process_time,
+            queue_depths: [queue.input_queue_size, queue.processing_queue_size, queue.output_queue_size],
+            frames_dropped: queue.frames_dropped + presentation.dropped as usize,
+            frames_repeated: presentation.repeated as usize,
+            frame_times: metrics.frame_times().to_vec(),
+            ..Self::default()
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let [input, processing, output] = self.queue_depths;
+        let mut lines = vec![
+            format!("FRAME {}", self.frame_index),
+            format!("PTS {:.1}MS", self.pts),
+            format!("FPS {:.1} AVG {:.1}", self.instant_fps, self.average_fps),
+            format!("DECODE {:.2}MS", self.decode_time),
+            format!("QUEUE {}/{}/{}", input, processing, output),
+            format!("DROPPED {} REPEATED {}", self.frames_dropped, self.frames_repeated),
+        ];
+        if let Some(bits) = self.packet_bits {
+            lines.push(format!("PACKET {} BITS", bits));
+        }
+        lines
+    }
+}
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const PADDING: usize = 4;
+const GRAPH_WIDTH: usize = 120;
+const GRAPH_HEIGHT: usize = 40;
+
+const BACKGROUND: [u8; 4] = [0, 0, 0, 160];
+const TEXT: [u8; 4] = [255, 255, 255, 255];
+const ON_TIME: [u8; 4] = [80, 220, 80, 255];
+const LATE: [u8; 4] = [230, 60, 60, 255];
+const BUDGET: [u8; 4] = [255, 220, 0, 255];
+
+// Renders the HUD as a transparent width x height RGBA overlay with the
+// panel in the top-left corner. Text scales up on larger canvases.
+pub fn draw_hud(width: usize, height: usize, stats: &HudStats) -> Frame {
+    let mut overlay = Frame::new(width, height);
+    let scale = (height / 360).max(1);
+    let lines = stats.lines();
+
+    let line_height = (GLYPH_HEIGHT + 2) * scale;
+    let text_width = lines.iter().map(|line| line.len()).max().unwrap_or(0) * (GLYPH_WIDTH + 1) * scale;
+    let graph_width = GRAPH_WIDTH * scale;
+    let graph_height = GRAPH_HEIGHT * scale;
+    let panel_width = text_width.max(graph_width) + PADDING * 2 * scale;
+    let panel_height = lines.len() * line_height + graph_height + PADDING * 3 * scale;
+
+    fill_rect(&mut overlay, 0, 0, panel_width, panel_height, BACKGROUND);
+
+    let mut y = PADDING * scale;
+    for line in &lines {
+        draw_text(&mut overlay, PADDING * scale, y, line, scale, TEXT);
+        y += line_height;
+    }
+
+    draw_frame_graph(&mut overlay, PADDING * scale, y + PADDING * scale, graph_width, graph_height, stats);
+    overlay
+}
+
+// One bar per frame time, newest on the right; the axis tops out at twice
+// the frame budget and the budget itself is drawn as a line
+fn draw_frame_graph(overlay: &mut Frame, x: usize, y: usize, width: usize, height: usize, stats: &HudStats) {
+    let budget = if stats.frame_budget > 0.0 { stats.frame_budget } else { 1000.0 / 60.0 };
+    let max_time = budget * 2.0;
+
+    let bars = stats.frame_times.len().min(width);
+    let bar_width = width.checked_div(bars).unwrap_or(1).max(1);
+    let recent = &stats.frame_times[stats.frame_times.len() - bars..];
+    for (i, &time) in recent.iter().enumerate() {
+        let bar_height = ((time / max_time).min(1.0) * height as f64).round() as usize;
+        let color = if time <= budget { ON_TIME } else { LATE };
+        let bar_x = x + width - (bars - i) * bar_width;
+        fill_rect(overlay, bar_x, y + height - bar_height, bar_width, bar_height, color);
+    }
+
+    fill_rect(overlay, x, y + height / 2, width, 1, BUDGET);
+}
+
+fn draw_text(overlay: &mut Frame, x: usize, y: usize, text: &str, scale: usize, color: [u8; 4]) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) != 0 {
+                    fill_rect(overlay, glyph_x + col * scale, y + row * scale, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+// Clipped to the overlay
+fn fill_rect(overlay: &mut Frame, x: usize, y: usize, width: usize, height: usize, color: [u8; 4]) {
+    let x_end = (x + width).min(overlay.width);
+    let y_end = (y + height).min(overlay.height);
+    for row in y.min(y_end)..y_end {
+        for col in x.min(x_end)..x_end {
+            let idx = (row * overlay.width + col) * 4;
+            overlay.data[idx..idx + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+// 5x7 glyphs, one byte per row with the leftmost pixel in bit 4
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        ' ' => [0x00; GLYPH_HEIGHT],
+        _ => [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Queue;
+    use crate::utils::clock::VirtualClock;
+    use std::rc::Rc;
+
+    fn pixel(frame: &Frame, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * frame.width + x) * 4;
+        frame.data[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_lines_show_stats() {
+        let stats = HudStats {
+            frame_index: 42,
+            pts: 1400.0,
+            queue_depths: [3, 1, 2],
+            packet_bits: Some(4096),
+            ..HudStats::default()
+        };
+
+        let lines = stats.lines();
+        assert_eq!(lines[0], "FRAME 42");
+        assert_eq!(lines[1], "PTS 1400.0MS");
+        assert_eq!(lines[4], "QUEUE 3/1/2");
+        assert_eq!(lines.last().unwrap(), "PACKET 4096 BITS");
+        assert_eq!(HudStats::default().lines().len(), 6);
+    }
+
+    #[test]
+    fn test_drops_include_late_frames() {
+        let metrics = Metrics::with_clock(Rc::new(VirtualClock::new(0.0)));
+        let mut queue = Queue::new(1, 1);
+        queue.push(Frame::new(1, 1));
+        queue.push(Frame::new(1, 1));
+        let presentation = PresentationStats { presented: 10, dropped: 3, repeated: 2, skipped: 4 };
+
+        let stats = HudStats::collect(&metrics, &queue.get_metrics(), &presentation);
+        assert_eq!(stats.lines()[5], "DROPPED 4 REPEATED 2");
+    }
+
+    #[test]
+    fn test_overlay_panel_and_graph() {
+        let stats = HudStats {
+            frame_times: vec![10.0, 50.0],
+            frame_budget: 20.0,
+            ..HudStats::default()
+        };
+        let overlay = draw_hud(320, 240, &stats);
+
+        // Panel in the corner, transparent elsewhere
+        assert_eq!(pixel(&overlay, 0, 0), BACKGROUND);
+        assert_eq!(pixel(&overlay, 319, 239), [0, 0, 0, 0]);
+        // 'F' of "FRAME" starts with a full row
+        assert_eq!(pixel(&overlay, PADDING, PADDING), TEXT);
+
+        // Graph bottom row: on-time bar then late bar, at the right edge
+        let lines = stats.lines().len();
+        let graph_top = PADDING + lines * (GLYPH_HEIGHT + 2) + PADDING;
+        let bottom = graph_top + GRAPH_HEIGHT - 1;
+        let right = PADDING + GRAPH_WIDTH - 1;
+        assert_eq!(pixel(&overlay, right, bottom), LATE);
+        assert_eq!(pixel(&overlay, right, graph_top), LATE);
+        assert_eq!(pixel(&overlay, right - GRAPH_WIDTH / 2, bottom), ON_TIME);
+        assert_eq!(pixel(&overlay, PADDING, graph_top + GRAPH_HEIGHT / 2), BUDGET);
+    }
+
+    #[test]
+    fn test_small_overlay_is_clipped() {
+        let overlay = draw_hud(8, 4, &HudStats::default());
+        assert_eq!((overlay.width, overlay.height), (8, 4));
+        assert_eq!(pixel(&overlay, 0, 0), BACKGROUND);
+    }
+}
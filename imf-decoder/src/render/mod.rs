@@ -0,0 +1,128 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{HtmlCanvasElement, ImageBitmap};
+use log::{info, warn};
+use crate::decoder::{Frame, GpuFilter};
+
+mod canvas2d;
+mod compare;
+mod headless;
+mod hud;
+mod webgl2;
+
+pub use canvas2d::{BitmapReader, Canvas2dRenderer};
+pub use compare::{difference_heatmap, CompareLayout, ComparePanels, CompareView};
+pub use headless::HeadlessRenderer;
+pub use hud::{draw_hud, HudStats};
+pub use webgl2::WebGl2Renderer;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RendererKind {
+    WebGl2 = 0,
+    Canvas2d = 1,
+    Headless = 2,
+}
+
+// Where presented frames go. Frames are RGBA and drawn from the top-left.
+pub trait Renderer {
+    fn kind(&self) -> RendererKind;
+
+    fn size(&self) -> (u32, u32);
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String>;
+
+    // Draws a browser bitmap over the current image with the given opacity
+    fn present_bitmap(&mut self, bitmap: &ImageBitmap, alpha: f64) -> Result<(), String>;
+
+    // Resizing drops the current image, like resizing a canvas
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String>;
+
+    fn clear(&mut self) -> Result<(), String>;
+
+    // Blends an RGBA overlay (straight alpha) over the current image at the
+    // top-left, unscaled. Overlays are drawn at the renderer's size.
+    fn draw_overlay(&mut self, overlay: &Frame) -> Result<(), String>;
+
+    // Reads back what is currently shown
+    fn capture(&self) -> Result<Frame, String>;
+
+    // Post-processing passes; only GPU backends run them
+    fn set_gpu_filters(&mut self, filters: &[GpuFilter]) -> Result<(), String> {
+        if filters.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{:?} renderer doesn't support GPU filters", self.kind()))
+        }
+    }
+}
+
+// Best backend first; headless works everywhere
+pub const DEFAULT_RENDERERS: [RendererKind; 3] = [
+    RendererKind::WebGl2,
+    RendererKind::Canvas2d,
+    RendererKind::Headless,
+];
+
+// Creates the first backend in `chain` that works, falling back to headless
+// if none does. Canvas backends need `canvas`; headless uses width x height.
+pub fn create_renderer(
+    canvas: Option<&HtmlCanvasElement>,
+    chain: &[RendererKind],
+    width: u32,
+    height: u32,
+) -> Box<dyn Renderer> {
+    for &kind in chain {
+        match try_create(kind, canvas, width, height) {
+            Ok(renderer) => {
+                info!("Using {:?} renderer", kind);
+                return renderer;
+            }
+            Err(e) => warn!("{:?} renderer unavailable: {}", kind, e),
+        }
+    }
+
+    info!("Falling back to headless renderer");
+    Box::new(HeadlessRenderer::new(width, height))
+}
+
+fn try_create(
+    kind: RendererKind,
+    canvas: Option<&HtmlCanvasElement>,
+    width: u32,
+    height: u32,
+) -> Result<Box<dyn Renderer>, String> {
+    match kind {
+        RendererKind::WebGl2 => {
+            let canvas = canvas.ok_or("No canvas to render into")?;
+            Ok(Box::new(WebGl2Renderer::new(canvas.clone())?))
+        }
+        RendererKind::Canvas2d => {
+            let canvas = canvas.ok_or("No canvas to render into")?;
+            Ok(Box::new(Canvas2dRenderer::new(canvas.clone())?))
+        }
+        RendererKind::Headless => Ok(Box::new(HeadlessRenderer::new(width, height))),
+    }
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_without_canvas_falls_back_to_headless() {
+        let renderer = create_renderer(None, &DEFAULT_RENDERERS, 8, 4);
+        assert_eq!(renderer.kind(), RendererKind::Headless);
+        assert_eq!(renderer.size(), (8, 4));
+
+        let mut renderer = create_renderer(None, &[RendererKind::Canvas2d], 2, 2);
+        assert_eq!(renderer.kind(), RendererKind::Headless);
+
+        // Headless can only run an empty GPU chain
+        assert!(renderer.set_gpu_filters(&[]).is_ok());
+        assert!(renderer.set_gpu_filters(&[GpuFilter::Denoise { strength: 0.5 }]).is_err());
+    }
+}
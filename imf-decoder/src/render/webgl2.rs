@@ -0,0 +1,57 @@
+use web_sys::{HtmlCanvasElement, ImageBitmap};
+use crate::decoder::{Frame, GpuFilter, WebGLDecoder};
+use super::{js_error, Renderer, RendererKind};
+
+// Presents through WebGLDecoder on the page canvas
+pub struct WebGl2Renderer {
+    decoder: WebGLDecoder,
+}
+
+impl WebGl2Renderer {
+    pub fn new(canvas: HtmlCanvasElement) -> Result<Self, String> {
+        Ok(Self {
+            decoder: WebGLDecoder::from_canvas(canvas).map_err(js_error)?,
+        })
+    }
+}
+
+impl Renderer for WebGl2Renderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::WebGl2
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let canvas = self.decoder.canvas();
+        (canvas.width(), canvas.height())
+    }
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        self.decoder.present(frame).map_err(js_error)
+    }
+
+    fn present_bitmap(&mut self, bitmap: &ImageBitmap, alpha: f64) -> Result<(), String> {
+        self.decoder.present_bitmap(bitmap, alpha).map_err(js_error)
+    }
+
+    fn draw_overlay(&mut self, overlay: &Frame) -> Result<(), String> {
+        self.decoder.present_overlay(overlay).map_err(js_error)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.decoder.resize(width, height);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), String> {
+        self.decoder.clear();
+        Ok(())
+    }
+
+    fn capture(&self) -> Result<Frame, String> {
+        self.decoder.capture().map_err(js_error)
+    }
+
+    fn set_gpu_filters(&mut self, filters: &[GpuFilter]) -> Result<(), String> {
+        self.decoder.set_filters(filters).map_err(js_error)
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use super::clock::{system_clock, SharedClock};
+
+// Window for the instantaneous rate, in milliseconds
+const RATE_WINDOW: f64 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    Reference,
+    Tokens,
+}
+
+// Byte counts of everything the decoder has been sent this session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BitrateStats {
+    pub reference_bytes: usize,
+    pub token_bytes: usize,
+    pub packets: usize,
+    pub frames: usize,
+    pub last_packet_bytes: usize,
+    // Over the last second
+    pub instant_kbps: f64,
+    // From the first packet until now
+    pub average_kbps: f64,
+    // Token bytes per token frame; the reference is reported separately
+    pub bytes_per_frame: f64,
+    // Share of all bytes spent on reference payloads, 0..=1
+    pub reference_share: f64,
+}
+
+pub struct BitrateMeter {
+    clock: SharedClock,
+    recent: VecDeque<(f64, usize)>,   // (arrival time, bytes) inside the rate window
+    session_start: Option<f64>,
+    reference_bytes: usize,
+    token_bytes: usize,
+    packets: usize,
+    frames: usize,
+    last_packet_bytes: usize,
+}
+
+impl BitrateMeter {
+    pub fn new() -> Self {
+        Self::with_clock(system_clock())
+    }
+
+    pub fn with_clock(clock: SharedClock) -> Self {
+        Self {
+            clock,
+            recent: VecDeque::new(),
+            session_start: None,
+            reference_bytes: 0,
+            token_bytes: 0,
+            packets: 0,
+            frames: 0,
+            last_packet_bytes: 0,
+        }
+    }
+
+    // Records one packet; `frames` is how many frame tokens it carried
+    pub fn record(&mut self, kind: PayloadKind, bytes: usize, frames: usize) {
+        let now = self.clock.now();
+        self.session_start.get_or_insert(now);
+
+        match kind {
+            PayloadKind::Reference => self.reference_bytes += bytes,
+            PayloadKind::Tokens => {
+                self.token_bytes += bytes;
+                self.frames += frames;
+            }
+        }
+        self.packets += 1;
+        self.last_packet_bytes = bytes;
+
+        self.recent.push_back((now, bytes));
+        self.expire(now);
+    }
+
+    pub fn stats(&self) -> BitrateStats {
+        let now = self.clock.now();
+        let total = self.reference_bytes + self.token_bytes;
+
+        let recent: usize = self.recent
+            .iter()
+            .filter(|(at, _)| now - at < RATE_WINDOW)
+            .map(|(_, bytes)| bytes)
+            .sum();
+
+        // A session shorter than the window is averaged over the window, so
+        // a single burst doesn't read as an enormous rate
+        let elapsed = self.session_start.map_or(0.0, |start| (now - start).max(RATE_WINDOW));
+        let average_kbps = if elapsed > 0.0 { kbps(total, elapsed) } else { 0.0 };
+
+        BitrateStats {
+            reference_bytes: self.reference_bytes,
+            token_bytes: self.token_bytes,
+            packets: self.packets,
+            frames: self.frames,
+            last_packet_bytes: self.last_packet_bytes,
+            instant_kbps: kbps(recent, RATE_WINDOW),
+            average_kbps,
+            bytes_per_frame: if self.frames > 0 { self.token_bytes as f64 / self.frames as f64 } else { 0.0 },
+            reference_share: if total > 0 { self.reference_bytes as f64 / total as f64 } else { 0.0 },
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.session_start = None;
+        self.reference_bytes = 0;
+        self.token_bytes = 0;
+        self.packets = 0;
+        self.frames = 0;
+        self.last_packet_bytes = 0;
+    }
+
+    fn expire(&mut self, now: f64) {
+        while self.recent.front().is_some_and(|(at, _)| now - at >= RATE_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+}
+
+impl Default for BitrateMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Bytes over milliseconds as kilobits per second
+fn kbps(bytes: usize, millis: f64) -> f64 {
+    bytes as f64 * 8.0 / millis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::utils::VirtualClock;
+
+    #[test]
+    fn test_rates_and_shares() {
+        let clock = Rc::new(VirtualClock::new(0.0));
+        let mut meter = BitrateMeter::with_clock(clock.clone());
+        assert_eq!(meter.stats(), BitrateStats::default());
+
+        meter.record(PayloadKind::Reference, 3000, 0);
+        clock.advance(500.0);
+        meter.record(PayloadKind::Tokens, 1000, 10);
+        clock.advance(500.0);
+        meter.record(PayloadKind::Tokens, 1000, 10);
+
+        let stats = meter.stats();
+        assert_eq!((stats.reference_bytes, stats.token_bytes), (3000, 2000));
+        assert_eq!((stats.packets, stats.frames, stats.last_packet_bytes), (3, 20, 1000));
+        assert_eq!(stats.bytes_per_frame, 100.0);
+        assert_eq!(stats.reference_share, 0.6);
+        // The reference arrived exactly a second ago and has left the window
+        assert_eq!(stats.instant_kbps, 16.0);
+        assert_eq!(stats.average_kbps, 40.0);
+
+        clock.advance(1000.0);
+        let stats = meter.stats();
+        assert_eq!(stats.instant_kbps, 0.0);
+        assert_eq!(stats.average_kbps, 20.0);
+    }
+
+    #[test]
+    fn test_short_sessions_average_over_the_window() {
+        let clock = Rc::new(VirtualClock::new(0.0));
+        let mut meter = BitrateMeter::with_clock(clock.clone());
+        meter.record(PayloadKind::Tokens, 500, 5);
+        assert_eq!(meter.stats().average_kbps, 4.0);
+
+        meter.reset();
+        assert_eq!(meter.stats(), BitrateStats::default());
+    }
+}
@@ -0,0 +1,136 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+
+// All timestamps are in milliseconds, matching `performance.now()`.
+pub trait Clock {
+    fn now(&self) -> f64;
+}
+
+// Shared handle used by Queue, Metrics and the player loop
+pub type SharedClock = Rc<dyn Clock>;
+
+// Backed by `performance.now()`, works in both window and worker scopes
+pub struct BrowserClock {
+    performance: Option<web_sys::Performance>,
+}
+
+impl BrowserClock {
+    pub fn new() -> Self {
+        let performance = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+            .ok()
+            .and_then(|p| p.dyn_into::<web_sys::Performance>().ok());
+        Self { performance }
+    }
+}
+
+impl Default for BrowserClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for BrowserClock {
+    fn now(&self) -> f64 {
+        match &self.performance {
+            Some(perf) => perf.now(),
+            None => js_sys::Date::now(),
+        }
+    }
+}
+
+// Native monotonic clock, measured from its own creation
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MonotonicClock {
+    origin: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            origin: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for MonotonicClock {
+    fn now(&self) -> f64 {
+        self.origin.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+// Manually advanced clock for deterministic tests. Clones share the same time,
+// so a test can keep one handle and pass another to the code under test.
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    now: Rc<Cell<f64>>,
+}
+
+impl VirtualClock {
+    pub fn new(start: f64) -> Self {
+        Self {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+
+    pub fn advance(&self, ms: f64) {
+        self.now.set(self.now.get() + ms);
+    }
+
+    pub fn set(&self, ms: f64) {
+        self.now.set(ms);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+}
+
+// Real-time clock for the current target
+pub fn system_clock() -> SharedClock {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Rc::new(BrowserClock::new())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Rc::new(MonotonicClock::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock_shared_between_clones() {
+        let clock = VirtualClock::new(100.0);
+        let handle: SharedClock = Rc::new(clock.clone());
+
+        clock.advance(16.5);
+        assert_eq!(handle.now(), 116.5);
+
+        clock.set(0.0);
+        assert_eq!(handle.now(), 0.0);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_monotonic_clock_never_goes_backwards() {
+        let clock = MonotonicClock::new();
+        let a = clock.now();
+        let b = clock.now();
+        assert!(b >= a);
+    }
+}
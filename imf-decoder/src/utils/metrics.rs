@@ -1,20 +1,56 @@
+use super::clock::{system_clock, SharedClock};
+
 pub struct Metrics {
     frame_times: Vec<f64>,
     queue_sizes: Vec<usize>,
     processing_times: Vec<f64>,
     window_size: usize,
+    clock: SharedClock,
+    last_frame_mark: Option<f64>,
 }
 
 impl Metrics {
     pub fn new() -> Self {
+        Self::with_clock(system_clock())
+    }
+
+    pub fn with_clock(clock: SharedClock) -> Self {
         Self {
             frame_times: Vec::new(),
             queue_sizes: Vec::new(),
             processing_times: Vec::new(),
             window_size: 60,
+            clock,
+            last_frame_mark: None,
         }
     }
 
+    pub fn now(&self) -> f64 {
+        self.clock.now()
+    }
+
+    // Records the time since the previous mark as a frame time
+    pub fn mark_frame(&mut self) {
+        let now = self.clock.now();
+        if let Some(last) = self.last_frame_mark {
+            self.record_frame_time(now - last);
+        }
+        self.last_frame_mark = Some(now);
+    }
+
+    // Time since the last mark_frame, if any
+    pub fn since_last_frame(&self) -> Option<f64> {
+        self.last_frame_mark.map(|last| self.clock.now() - last)
+    }
+
+    // Runs `f` and records how long it took as a processing time
+    pub fn time_processing<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let start = self.clock.now();
+        let result = f();
+        self.record_processing_time(self.clock.now() - start);
+        result
+    }
+
     pub fn record_frame_time(&mut self, time: f64) {
         self.frame_times.push(time);
         if self.frame_times.len() > self.window_size {
@@ -35,4 +71,80 @@ impl Metrics {
             self.processing_times.remove(0);
         }
     }
-}
\ No newline at end of file
+
+    // Recent frame times, oldest first
+    pub fn frame_times(&self) -> &[f64] {
+        &self.frame_times
+    }
+
+    pub fn last_frame_time(&self) -> Option<f64> {
+        self.frame_times.last().copied()
+    }
+
+    pub fn average_frame_time(&self) -> f64 {
+        average(&self.frame_times)
+    }
+
+    pub fn average_processing_time(&self) -> f64 {
+        average(&self.processing_times)
+    }
+
+    pub fn fps(&self) -> f64 {
+        let frame_time = self.average_frame_time();
+        if frame_time > 0.0 {
+            1000.0 / frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn average(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::VirtualClock;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_frame_times_follow_clock() {
+        let clock = VirtualClock::new(0.0);
+        let mut metrics = Metrics::with_clock(Rc::new(clock.clone()));
+
+        metrics.mark_frame();
+        for _ in 0..4 {
+            clock.advance(20.0);
+            metrics.mark_frame();
+        }
+
+        assert_eq!(metrics.average_frame_time(), 20.0);
+        assert_eq!(metrics.fps(), 50.0);
+    }
+
+    #[test]
+    fn test_processing_time_measured() {
+        let clock = VirtualClock::new(0.0);
+        let mut metrics = Metrics::with_clock(Rc::new(clock.clone()));
+
+        let value = metrics.time_processing(|| {
+            clock.advance(7.5);
+            42
+        });
+
+        assert_eq!(value, 42);
+        assert_eq!(metrics.average_processing_time(), 7.5);
+    }
+}
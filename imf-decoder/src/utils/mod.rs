@@ -1,5 +1,11 @@
+pub mod bitrate;
+pub mod clock;
 pub mod memory;
 pub mod metrics;
+pub mod png;
 
+pub use bitrate::{BitrateMeter, BitrateStats, PayloadKind};
+pub use clock::{Clock, SharedClock, VirtualClock};
 pub use memory::Memory;
-pub use metrics::Metrics;
\ No newline at end of file
+pub use metrics::Metrics;
+pub use png::encode_png;
\ No newline at end of file
@@ -0,0 +1,154 @@
+use crate::decoder::Frame;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Largest payload of a stored deflate block
+const MAX_STORED_BLOCK: usize = 65_535;
+
+// Encodes a frame as an 8-bit RGBA PNG, converting YUV first. Image data is
+// zlib-wrapped with stored (uncompressed) deflate blocks, which every
+// decoder reads and keeps the encoder small.
+pub fn encode_png(frame: &Frame) -> Result<Vec<u8>, String> {
+    let frame = frame.as_rgba()?;
+    let row = frame.width * 4;
+
+    // Each scanline starts with its filter type, 0 = none
+    let mut raw = Vec::with_capacity((row + 1) * frame.height);
+    for line in frame.data.chunks_exact(row.max(1)).take(frame.height) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(frame.width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(frame.height as u32).to_be_bytes());
+    // Bit depth 8, color type 6 (RGBA), deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    // Deflate with a 32K window, no preset dictionary, fastest
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 }
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65_521;
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % MOD;
+        (a, (b + a) % MOD)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits a PNG into (type, data) chunks, checking each CRC
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut pos = 8;
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &png[pos + 4..pos + 8 + len];
+            let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            chunks.push((body[..4].try_into().unwrap(), body[4..].to_vec()));
+            pos += 12 + len;
+        }
+        chunks
+    }
+
+    // Reads back zlib data made of stored blocks
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut pos = 2;
+        loop {
+            let last = zlib[pos] & 1 == 1;
+            assert_eq!(zlib[pos] >> 1, 0, "expected a stored block");
+            let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]) as usize;
+            assert_eq!(!u16::from_le_bytes([zlib[pos + 3], zlib[pos + 4]]) as usize, len);
+            out.extend_from_slice(&zlib[pos + 5..pos + 5 + len]);
+            pos += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(zlib[pos..], adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_encodes_rgba_frame() {
+        let mut frame = Frame::new(3, 2);
+        frame.set_data((0..24).collect());
+
+        let chunks = chunks(&encode_png(&frame).unwrap());
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+
+        let raw = inflate_stored(&chunks[1].1);
+        assert_eq!(raw.len(), 2 * (1 + 12));
+        assert_eq!(raw[0], 0);
+        assert_eq!(raw[1..13], frame.data[..12]);
+        assert_eq!(raw[14..], frame.data[12..]);
+    }
+
+    #[test]
+    fn test_large_frames_span_several_blocks() {
+        let frame = Frame::new(200, 100);
+        let chunks = chunks(&encode_png(&frame).unwrap());
+        let raw = inflate_stored(&chunks[1].1);
+        assert_eq!(raw.len(), 100 * (1 + 800));
+        assert!(raw.iter().all(|&byte| byte == 0));
+    }
+}
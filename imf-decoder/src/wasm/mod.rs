@@ -1,4 +1,6 @@
 pub mod bindings;
+mod render_loop;
+pub(crate) mod state;
 
 // Re-export the bindings
 pub use bindings::*;
\ No newline at end of file
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use log::error;
+
+// The browser timer the next tick is waiting on
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PendingTick {
+    AnimationFrame(i32),
+    Timeout(i32),
+}
+
+#[derive(Default)]
+struct LoopState {
+    callback: Option<Closure<dyn FnMut()>>,
+    pending: Option<PendingTick>,
+    // Bumped by every start/stop so a tick from an old run never reschedules
+    generation: u64,
+}
+
+// Drives a tick function from requestAnimationFrame or setTimeout. The tick
+// returns how many ms to wait before the next one, or None to stop.
+//
+// The stored closure only holds a weak reference to the loop, so dropping the
+// owner (even from inside a tick) frees everything without dangling pointers;
+// wasm-bindgen defers freeing a closure that is still executing.
+pub struct RenderLoop {
+    state: Rc<RefCell<LoopState>>,
+}
+
+impl RenderLoop {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(LoopState::default())),
+        }
+    }
+
+    pub fn start(&self, mut tick: impl FnMut() -> Option<f64> + 'static) -> Result<(), JsValue> {
+        self.stop();
+
+        let generation = self.state.borrow().generation;
+        let weak = Rc::downgrade(&self.state);
+        let callback = Closure::<dyn FnMut()>::new(move || {
+            let Some(state) = weak.upgrade() else { return };
+            state.borrow_mut().pending = None;
+
+            // No borrow is held while ticking, so the tick may stop or restart us
+            let next = tick();
+
+            if state.borrow().generation != generation {
+                return;
+            }
+            if let Some(delay) = next {
+                if let Err(e) = schedule(&state, delay) {
+                    error!("Failed to schedule next frame: {:?}", e);
+                }
+            }
+        });
+
+        self.state.borrow_mut().callback = Some(callback);
+        schedule(&self.state, 0.0)
+    }
+
+    // Cancels whichever timer is pending. The closure stays alive until the
+    // next start or drop, in case it is the one currently running.
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        state.generation += 1;
+        if let Some(pending) = state.pending.take() {
+            cancel(pending);
+        }
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.state.borrow().pending.is_some()
+    }
+}
+
+impl Default for RenderLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for RenderLoop {
+    fn drop(&mut self) {
+        self.stop();
+        self.state.borrow_mut().callback = None;
+    }
+}
+
+// Waits `delay` ms with setTimeout, or the next display refresh if no delay
+fn schedule(state: &Rc<RefCell<LoopState>>, delay: f64) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window available"))?;
+    let mut state = state.borrow_mut();
+    let Some(callback) = state.callback.as_ref() else {
+        return Ok(());
+    };
+    let function = callback.as_ref().unchecked_ref();
+
+    let delay = delay.min(1000.0) as i32;
+    let pending = if delay > 0 {
+        PendingTick::Timeout(
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(function, delay)?,
+        )
+    } else {
+        PendingTick::AnimationFrame(window.request_animation_frame(function)?)
+    };
+
+    state.pending = Some(pending);
+    Ok(())
+}
+
+fn cancel(pending: PendingTick) {
+    let Some(window) = web_sys::window() else { return };
+    match pending {
+        PendingTick::AnimationFrame(id) => {
+            if let Err(e) = window.cancel_animation_frame(id) {
+                error!("Failed to cancel animation frame: {:?}", e);
+            }
+        }
+        PendingTick::Timeout(id) => window.clear_timeout_with_handle(id),
+    }
+}
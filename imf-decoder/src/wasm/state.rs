@@ -0,0 +1,1687 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{ImageBitmap, HtmlCanvasElement};
+use serde::{Serialize, Deserialize};
+use log::{info, error, debug, warn};
+use crate::decoder::{
+    embed_barcode, generate_signal, AdaptiveConfig, AdaptiveRateController, FilterChain, FilterConfig, Frame,
+    FrameAnalyzer, FrameStamp, GpuFilter, FrameSequencer, PlaybackMode, Presentation, PresentationScheduler,
+    Queue as FrameQueue, QueueStats, Step, TestSignal, TokenPacket, TokenStream,
+};
+use crate::decoder::stream::decode_token;
+use crate::render::{
+    create_renderer, draw_hud, BitmapReader, ComparePanels, CompareView, HudStats, Renderer, RendererKind,
+    DEFAULT_RENDERERS,
+};
+use crate::quality::{compare_frames, FrameQuality, QualityLog, TemporalTracker};
+use crate::utils::{encode_png, BitrateMeter, Metrics, PayloadKind, SharedClock};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ReferenceFeature {
+    tensor: Vec<f32>,
+    shape: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ReferenceData {
+    features: Vec<ReferenceFeature>,
+    token: Vec<f32>,
+}
+
+// Bytes of a tensor as it arrived from JS: typed arrays by their byte length,
+// plain arrays as the doubles JS numbers are stored in
+fn tensor_bytes(value: &JsValue) -> usize {
+    if let Some(array) = value.dyn_ref::<js_sys::Array>() {
+        return array.length() as usize * std::mem::size_of::<f64>();
+    }
+    if js_sys::ArrayBuffer::is_view(value) {
+        return field(value, "byteLength").as_f64().unwrap_or(0.0) as usize;
+    }
+    0
+}
+
+fn field(value: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(value, &name.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+// Token bytes in a `[{ frame_index, token, is_keyframe }]` batch as received
+pub(crate) fn token_payload_bytes(packets: &JsValue) -> usize {
+    js_sys::Array::from(packets).iter().map(|packet| tensor_bytes(&field(&packet, "token"))).sum()
+}
+
+// Feature tensor and token bytes of a reference payload as received
+fn reference_payload_bytes(data: &JsValue) -> usize {
+    let features: usize = js_sys::Array::from(&field(data, "features"))
+        .iter()
+        .map(|feature| tensor_bytes(&field(&feature, "tensor")))
+        .sum();
+    features + tensor_bytes(&field(data, "token"))
+}
+
+// Everything the decoder and its render loop share. IMFDecoder owns it behind
+// Rc<RefCell<..>> and the loop closure only holds a weak reference.
+pub(crate) struct DecoderState {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    frame_queue: FrameQueue,
+    renderer: Option<Box<dyn Renderer>>, // Where frames are drawn, set up by attach_canvas/attach_headless
+    renderer_chain: Vec<RendererKind>,   // Backends to try, best first
+    gpu_filters: Vec<GpuFilter>,         // Post-processing chain, reapplied to new renderers
+    cpu_filters: Vec<FilterConfig>,      // Filters run in the queue's processing stage
+    reference_data: Option<ReferenceData>,
+    pub(crate) diagnostic_mode: bool,
+    pub(crate) debug_mode: bool,
+    test_signal: TestSignal,           // What debug mode shows
+    signal_barcode: bool,              // Stamp debug frames with a frame counter barcode
+    frame_count: u64,
+    is_playing: bool,
+    frames: Vec<ImageBitmap>,          // Store loaded frames
+    sequencer: FrameSequencer,         // Current frame index, direction and playback mode
+    target_fps: u32,                   // Target frame rate
+    frame_interval: f64,               // Target time between frames
+    metrics: Metrics,                  // Render-side frame timing
+    scheduler: PresentationScheduler,  // Decides which frame is due on screen
+    adaptive_fps: bool,                // Let measured throughput pick the frame rate
+    rate_controller: AdaptiveRateController,
+    playback_callback: Option<js_sys::Function>, // Receives ended/looped/reversed events
+    pending_events: Vec<JsValue>,      // Playback events raised during a render
+    token_stream: TokenStream,         // Received tokens, kept for seeking
+    last_frame: Option<Frame>,         // Last decoded frame drawn, redrawn when none is ready
+    min_render_interval: f64,          // Render throttle set by adaptive FPS, 0 = every refresh
+    frame_blending: bool,              // Cross-fade neighbouring frames in slow motion
+    compare_view: Option<CompareView>, // Ground truth / reference / decoded comparison
+    bitmap_reader: Option<BitmapReader>, // Scratch canvas for reading loaded frames back
+    frame_analyzer: Option<FrameAnalyzer>, // Checks barcodes in what actually reaches the canvas
+    quality_log: Option<QualityLog>,   // Decoded vs ground-truth quality per frame index
+    bitrate: BitrateMeter,             // Bytes received as reference and token payloads
+}
+
+impl DecoderState {
+    pub(crate) fn new(width: u32, height: u32, clock: SharedClock) -> Self {
+        let target_fps = 30; // Set target FPS
+        let frame_interval = 1000.0 / target_fps as f64; // Calculate interval in ms
+
+        Self {
+            width,
+            height,
+            frame_queue: FrameQueue::with_clock(10000, 4, clock.clone()),
+            renderer: None,
+            renderer_chain: DEFAULT_RENDERERS.to_vec(),
+            gpu_filters: Vec::new(),
+            cpu_filters: Vec::new(),
+            reference_data: None,
+            diagnostic_mode: false,
+            debug_mode: false,
+            test_signal: TestSignal::default(),
+            signal_barcode: false,
+            frame_count: 0,
+            is_playing: false,
+            frames: Vec::new(),
+            sequencer: FrameSequencer::new(PlaybackMode::PingPong),
+            target_fps,
+            frame_interval,
+            metrics: Metrics::with_clock(clock.clone()),
+            bitrate: BitrateMeter::with_clock(clock.clone()),
+            scheduler: PresentationScheduler::new(clock, frame_interval),
+            adaptive_fps: false,
+            rate_controller: Self::new_rate_controller(target_fps),
+            playback_callback: None,
+            pending_events: Vec::new(),
+            token_stream: TokenStream::new(),
+            last_frame: None,
+            min_render_interval: 0.0,
+            frame_blending: false,
+            compare_view: None,
+            bitmap_reader: None,
+            frame_analyzer: None,
+            quality_log: None,
+        }
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    // Returns false if playback was already running
+    pub(crate) fn start_playback(&mut self) -> bool {
+        if self.is_playing {
+            return false;
+        }
+        self.is_playing = true;
+
+        // Play-once restarts from the beginning after it ended
+        if self.sequencer.is_ended() {
+            self.sequencer.rewind(self.frames.len());
+        }
+
+        self.scheduler.start();
+        true
+    }
+
+    pub(crate) fn stop_playback(&mut self) {
+        self.is_playing = false;
+        self.scheduler.pause();
+    }
+
+    pub(crate) fn set_frames(&mut self, frames: Vec<ImageBitmap>) {
+        let frame_count = frames.len();
+        self.frames = frames;
+        self.sequencer.rewind(frame_count);
+    }
+
+    // Sleep until the next frame is due (and the render throttle allows
+    // drawing), then let the scheduler decide
+    pub(crate) fn next_frame_delay(&self) -> f64 {
+        if self.is_blending() {
+            return 0.0;
+        }
+
+        let deadline = self.scheduler.time_until_next_deadline();
+        let throttle = self.metrics.since_last_frame()
+            .map_or(0.0, |since| self.min_render_interval - since);
+        deadline.max(throttle)
+    }
+
+    pub(crate) fn set_target_fps(&mut self, fps: u32) {
+        self.target_fps = fps.clamp(1, 60); // Clamp between 1 and 60 FPS
+        self.frame_interval = 1000.0 / self.target_fps as f64;
+        info!("Target FPS set to: {} (interval: {}ms)", self.target_fps, self.frame_interval);
+
+        self.scheduler.set_frame_interval(self.frame_interval);
+
+        // In adaptive mode the target is the ceiling for the effective rate
+        let change = self.rate_controller.set_max_fps(self.target_fps);
+        if let (true, Some(change)) = (self.adaptive_fps, change) {
+            self.apply_effective_fps(change.fps);
+        }
+    }
+
+    pub(crate) fn target_fps(&self) -> u32 {
+        self.target_fps
+    }
+
+    pub(crate) fn set_adaptive_fps(&mut self, enabled: bool) {
+        self.adaptive_fps = enabled;
+        self.rate_controller = Self::new_rate_controller(self.target_fps);
+        self.min_render_interval = 0.0;
+        info!("Adaptive FPS {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    pub(crate) fn is_adaptive_fps(&self) -> bool {
+        self.adaptive_fps
+    }
+
+    // Rate the player is actually running at (equals target_fps unless adaptive)
+    pub(crate) fn effective_fps(&self) -> u32 {
+        if self.adaptive_fps {
+            self.rate_controller.fps()
+        } else {
+            self.target_fps
+        }
+    }
+
+    // Recent adaptive rate changes, oldest first
+    pub(crate) fn rate_changes(&self) -> Result<JsValue, JsValue> {
+        let changes: Vec<_> = self.rate_controller.history().collect();
+        Ok(serde_wasm_bindgen::to_value(&changes)?)
+    }
+
+    fn new_rate_controller(max_fps: u32) -> AdaptiveRateController {
+        AdaptiveRateController::new(AdaptiveConfig {
+            max_fps,
+            ..AdaptiveConfig::default()
+        })
+    }
+
+    // Caps how often we draw; content keeps its own pace and the scheduler
+    // drops the frames we skip
+    fn apply_effective_fps(&mut self, fps: u32) {
+        self.min_render_interval = 1000.0 / fps as f64;
+    }
+
+    // Playback speed, 0.25x to 4x either way. The sign multiplies the mode's
+    // direction: ReverseLoop at a negative rate plays forwards and PingPong
+    // starts back the other way.
+    pub(crate) fn set_playback_rate(&mut self, rate: f64) -> Result<(), JsValue> {
+        if !(0.25..=4.0).contains(&rate.abs()) {
+            return Err(JsValue::from_str(&format!(
+                "Playback rate {} out of range, expected 0.25 to 4 (or -4 to -0.25)", rate
+            )));
+        }
+
+        let was_queued = self.uses_decode_queue();
+        self.sequencer.set_reversed(rate < 0.0);
+        self.scheduler.set_rate(rate);
+        self.resume_decode_queue(was_queued)?;
+
+        info!("Playback rate set to: {}x", rate);
+        Ok(())
+    }
+
+    pub(crate) fn playback_rate(&self) -> f64 {
+        self.scheduler.rate()
+    }
+
+    pub(crate) fn set_frame_blending(&mut self, enabled: bool) {
+        self.frame_blending = enabled;
+    }
+
+    pub(crate) fn frame_blending(&self) -> bool {
+        self.frame_blending
+    }
+
+    // Slow-motion bitmap playback redraws every refresh to cross-fade frames
+    fn is_blending(&self) -> bool {
+        self.frame_blending && !self.debug_mode && self.compare_view.is_none() && self.scheduler.rate().abs() < 1.0
+    }
+
+    // Feeds this frame's decode and render cost to the adaptive controller
+    fn update_adaptive_rate(&mut self, render_time: f64) {
+        if !self.adaptive_fps {
+            return;
+        }
+
+        let decode_time = self.frame_queue.get_metrics().average_processing_time;
+        if let Some(change) = self.rate_controller.observe(decode_time, render_time) {
+            info!(
+                "Adaptive FPS {} -> {} ({:?}, decode {:.2}ms, render {:.2}ms)",
+                change.previous_fps, change.fps, change.reason, change.decode_time, change.render_time
+            );
+            self.apply_effective_fps(change.fps);
+        }
+    }
+
+    // Debug-mode frame for media slot `slot`: the selected test signal,
+    // stamped with the slot and its timestamp when the barcode is on, so slots
+    // the scheduler passes over show up as gaps
+    fn generate_test_frame(&self, slot: u64) -> Frame {
+        let mut frame = generate_signal(self.test_signal, self.width as usize, self.height as usize, slot);
+        frame.frame_index = slot as usize;
+        frame.timestamp = slot as f64 * self.frame_interval;
+
+        if self.signal_barcode {
+            let stamp = FrameStamp {
+                frame_index: slot as u32,
+                timestamp_ms: frame.timestamp.round() as u32,
+            };
+            if !embed_barcode(&mut frame, stamp) {
+                debug!("Frame too small for a barcode");
+            }
+        }
+        frame
+    }
+
+    pub(crate) fn set_test_signal(&mut self, signal: TestSignal, barcode: bool) {
+        self.test_signal = signal;
+        self.signal_barcode = barcode;
+    }
+
+    pub(crate) fn test_signal(&self) -> TestSignal {
+        self.test_signal
+    }
+
+    // Tries `kind` before the remaining backends next time a target is attached
+    pub(crate) fn set_preferred_renderer(&mut self, kind: RendererKind) {
+        self.renderer_chain.retain(|&other| other != kind);
+        self.renderer_chain.insert(0, kind);
+    }
+
+    pub(crate) fn renderer_kind(&self) -> Option<RendererKind> {
+        self.renderer.as_ref().map(|renderer| renderer.kind())
+    }
+
+    pub(crate) fn attach_canvas(&mut self, canvas: &HtmlCanvasElement) -> RendererKind {
+        // Frames are decoded at the canvas size
+        self.width = canvas.width();
+        self.height = canvas.height();
+        self.set_renderer(create_renderer(Some(canvas), &self.renderer_chain, self.width, self.height))
+    }
+
+    pub(crate) fn attach_headless(&mut self, width: u32, height: u32) -> RendererKind {
+        self.width = width;
+        self.height = height;
+        self.set_renderer(create_renderer(None, &[RendererKind::Headless], width, height))
+    }
+
+    fn set_renderer(&mut self, mut renderer: Box<dyn Renderer>) -> RendererKind {
+        let kind = renderer.kind();
+        if let Err(e) = renderer.set_gpu_filters(&self.gpu_filters) {
+            warn!("GPU filters disabled: {}", e);
+        }
+        self.renderer = Some(renderer);
+        self.last_frame = None;
+        kind
+    }
+
+    // Array of { type: "unsharp_mask" | "contrast_adaptive_sharpen" | "denoise" | "bicubic_upscale", ...params }
+    pub(crate) fn set_gpu_filters(&mut self, filters: JsValue) -> Result<(), JsValue> {
+        let filters: Vec<GpuFilter> = serde_wasm_bindgen::from_value(filters)?;
+        for filter in &filters {
+            filter.validate().map_err(|e| JsValue::from_str(&e))?;
+        }
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_gpu_filters(&filters).map_err(|e| JsValue::from_str(&e))?;
+        }
+        info!("GPU filter chain set to {:?}", filters);
+        self.gpu_filters = filters;
+        Ok(())
+    }
+
+    pub(crate) fn gpu_filters(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.gpu_filters)?)
+    }
+
+    // Array of { type: "sharpen" | "temporal_smooth" | "levels" | "edge_aware_upscale", ...params }
+    pub(crate) fn set_cpu_filters(&mut self, filters: JsValue) -> Result<(), JsValue> {
+        let configs: Vec<FilterConfig> = serde_wasm_bindgen::from_value(filters)?;
+        let chain = FilterChain::from_configs(&configs).map_err(|e| JsValue::from_str(&e))?;
+
+        info!("CPU filter chain set to {:?}", chain.names());
+        self.frame_queue.set_filters(chain);
+        self.cpu_filters = configs;
+        Ok(())
+    }
+
+    pub(crate) fn cpu_filters(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.cpu_filters)?)
+    }
+
+    // { layout: "side_by_side" | "split_wipe", wipe, heatmap, gain }; null turns
+    // the comparison off. Needs ground-truth frames from load_frames.
+    pub(crate) fn set_compare_view(&mut self, view: JsValue) -> Result<(), JsValue> {
+        if view.is_null() || view.is_undefined() {
+            self.compare_view = None;
+            // Back to the decode size the comparison widened
+            if let Some(renderer) = self.renderer.as_mut() {
+                renderer.resize(self.width, self.height).map_err(|e| JsValue::from_str(&e))?;
+            }
+            return Ok(());
+        }
+
+        let view: CompareView = serde_wasm_bindgen::from_value(view)?;
+        view.validate().map_err(|e| JsValue::from_str(&e))?;
+        info!("Comparison view set to {:?}", view);
+        self.compare_view = Some(view);
+
+        if !self.frames.is_empty() {
+            self.present_comparison(self.sequencer.current())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn compare_view(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.compare_view)?)
+    }
+
+    pub(crate) fn resize_renderer(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        let renderer = self.renderer.as_mut()
+            .ok_or_else(|| JsValue::from_str("No render target attached"))?;
+        renderer.resize(width, height).map_err(|e| JsValue::from_str(&e))?;
+        self.width = width;
+        self.height = height;
+
+        // Resizing wipes the image; put the last frame back if we have one
+        self.redraw_last_frame()?;
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> JsValue {
+        let capabilities = js_sys::Object::new();
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"version".into(),
+            &"1.0.0".into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"dimensions".into(),
+            &format!("{}x{}", self.width, self.height).into()
+        ).unwrap();
+
+        // Create features array
+        let features = js_sys::Array::new();
+        features.push(&"WebGPU".into());
+        features.push(&"Tensor Processing".into());
+        features.push(&"Frame Queue".into());
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"features".into(),
+            &features
+        ).unwrap();
+
+        // Create methods array
+        let methods = js_sys::Array::new();
+        methods.push(&"test".into());
+        methods.push(&"start_player_loop".into());
+        methods.push(&"stop_player_loop".into());
+        methods.push(&"set_reference_data".into());
+        methods.push(&"process_tokens".into());
+        methods.push(&"process_batch".into());
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"methods".into(),
+            &methods
+        ).unwrap();
+
+        // Add performance capabilities
+        let performance = js_sys::Object::new();
+        js_sys::Reflect::set(
+            &performance,
+            &"maxQueueSize".into(),
+            &(60_i32).into()
+        ).unwrap();
+        js_sys::Reflect::set(
+            &performance,
+            &"batchSize".into(),
+            &(4_i32).into()
+        ).unwrap();
+        js_sys::Reflect::set(
+            &performance,
+            &"targetFPS".into(),
+            &(60_i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"performance".into(),
+            &performance
+        ).unwrap();
+
+        // Add diagnostic info
+        let diagnostics = js_sys::Object::new();
+
+        js_sys::Reflect::set(
+            &diagnostics,
+            &"diagnosticMode".into(),
+            &self.diagnostic_mode.into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &capabilities,
+            &"diagnostics".into(),
+            &diagnostics
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &diagnostics,
+            &"frameCount".into(),
+            &JsValue::from_f64(self.frame_count as f64)
+        ).unwrap();
+
+        capabilities.into()
+    }
+
+    pub(crate) fn status(&self, loop_scheduled: bool) -> JsValue {
+        let status = js_sys::Object::new();
+
+        js_sys::Reflect::set(
+            &status,
+            &"initialized".into(),
+            &self.renderer.is_some().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &status,
+            &"renderer".into(),
+            &self.renderer_kind().map(|kind| JsValue::from(format!("{:?}", kind))).unwrap_or(JsValue::NULL)
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &status,
+            &"running".into(),
+            &(self.is_playing || loop_scheduled).into()
+        ).unwrap();
+
+
+        // Performance metrics
+        let metrics = js_sys::Object::new();
+        js_sys::Reflect::set(
+            &metrics,
+            &"frameCount".into(),
+            &JsValue::from_f64(self.frame_count as f64)
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &metrics,
+            &"fps".into(),
+            &self.metrics.fps().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &metrics,
+            &"averageFrameTime".into(),
+            &self.metrics.average_frame_time().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(&status, &"metrics".into(), &metrics).unwrap();
+
+        // Presentation scheduler
+        let presentation = self.scheduler.stats();
+        let playback = js_sys::Object::new();
+        js_sys::Reflect::set(
+            &playback,
+            &"mediaTime".into(),
+            &self.scheduler.media_time().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &playback,
+            &"rate".into(),
+            &self.scheduler.rate().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &playback,
+            &"framesPresented".into(),
+            &JsValue::from_f64(presentation.presented as f64)
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &playback,
+            &"framesDropped".into(),
+            &JsValue::from_f64(presentation.dropped as f64)
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &playback,
+            &"framesRepeated".into(),
+            &JsValue::from_f64(presentation.repeated as f64)
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &playback,
+            &"framesSkipped".into(),
+            &JsValue::from_f64(presentation.skipped as f64)
+        ).unwrap();
+
+        js_sys::Reflect::set(&status, &"playback".into(), &playback).unwrap();
+
+        // Adaptive frame rate
+        let adaptive = js_sys::Object::new();
+        js_sys::Reflect::set(
+            &adaptive,
+            &"enabled".into(),
+            &self.adaptive_fps.into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &adaptive,
+            &"effectiveFps".into(),
+            &self.effective_fps().into()
+        ).unwrap();
+
+        let last_reason = self.rate_controller.last_change()
+            .map(|change| format!("{:?}", change.reason));
+        js_sys::Reflect::set(
+            &adaptive,
+            &"lastReason".into(),
+            &last_reason.map(JsValue::from).unwrap_or(JsValue::NULL)
+        ).unwrap();
+
+        js_sys::Reflect::set(&status, &"adaptive".into(), &adaptive).unwrap();
+
+
+
+        // Bandwidth
+        let bitrate = self.bitrate.stats();
+        let bandwidth = js_sys::Object::new();
+        for (key, value) in [
+            ("instantKbps", bitrate.instant_kbps),
+            ("averageKbps", bitrate.average_kbps),
+            ("bytesPerFrame", bitrate.bytes_per_frame),
+            ("referenceShare", bitrate.reference_share),
+            ("referenceBytes", bitrate.reference_bytes as f64),
+            ("tokenBytes", bitrate.token_bytes as f64),
+            ("lastPacketBytes", bitrate.last_packet_bytes as f64),
+            ("packets", bitrate.packets as f64),
+        ] {
+            js_sys::Reflect::set(&bandwidth, &key.into(), &JsValue::from_f64(value)).unwrap();
+        }
+
+        js_sys::Reflect::set(&status, &"bitrate".into(), &bandwidth).unwrap();
+
+        // Queue status using correct method names
+        let queue_status = js_sys::Object::new();
+        let (input_size, processing_size, output_size) = self.frame_queue.get_queue_sizes();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"inputQueueSize".into(),
+            &(input_size as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"processingQueueSize".into(),
+            &(processing_size as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"outputQueueSize".into(),
+            &(output_size as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"maxSize".into(),
+            &(self.frame_queue.get_max_size() as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"batchSize".into(),
+            &(self.frame_queue.get_batch_size() as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"isFull".into(),
+            &self.frame_queue.is_full().into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"isEmpty".into(),
+            &self.frame_queue.is_empty().into()
+        ).unwrap();
+
+        // Add additional metrics
+        js_sys::Reflect::set(
+            &queue_status,
+            &"framesProcessed".into(),
+            &(self.frame_queue.get_frames_processed() as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"framesDropped".into(),
+            &(self.frame_queue.get_frames_dropped() as i32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"processingTime".into(),
+            &self.frame_queue.get_processing_time().into()
+        ).unwrap();
+
+        // Add queue stats
+        let stats = self.frame_queue.get_metrics();
+        let queue_metrics = js_sys::Object::new();
+
+        js_sys::Reflect::set(
+            &queue_metrics,
+            &"averageProcessingTime".into(),
+            &stats.average_processing_time.into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_metrics,
+            &"queueUtilization".into(),
+            &stats.queue_utilization.into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &queue_status,
+            &"metrics".into(),
+            &queue_metrics
+        ).unwrap();
+
+        js_sys::Reflect::set(&status, &"queue".into(), &queue_status).unwrap();
+
+        // Debug info
+        let debug = js_sys::Object::new();
+        js_sys::Reflect::set(
+            &debug,
+            &"diagnosticMode".into(),
+            &self.diagnostic_mode.into()
+        ).unwrap();
+
+        js_sys::Reflect::set(&status, &"debug".into(), &debug).unwrap();
+
+        status.into()
+    }
+
+    pub(crate) fn set_reference_data(&mut self, data: JsValue) -> Result<String, JsValue> {
+        info!("Setting reference data...");
+
+        let payload_bytes = reference_payload_bytes(&data);
+        let ref_data: ReferenceData = serde_wasm_bindgen::from_value(data)?;
+
+        let expected_shapes = [
+            vec![1, 128, 64, 64],
+            vec![1, 256, 32, 32],
+            vec![1, 512, 16, 16],
+            vec![1, 512, 8, 8],
+        ];
+
+        for (feature, expected) in ref_data.features.iter().zip(expected_shapes.iter()) {
+            if feature.shape != *expected {
+                return Err(JsValue::from_str(&format!(
+                    "Invalid tensor shape: {:?}, expected: {:?}",
+                    feature.shape, expected
+                )));
+            }
+        }
+
+        if ref_data.token.len() != 32 {
+            return Err(JsValue::from_str("Reference token must be length 32"));
+        }
+
+        self.bitrate.record(PayloadKind::Reference, payload_bytes, 0);
+        self.reference_data = Some(ref_data);
+
+        // Tokens are relative to the reference, so a new one starts a new stream
+        self.token_stream.clear();
+        self.frame_queue.flush();
+        self.last_frame = None;
+        Ok("Reference data set successfully".to_string())
+    }
+
+    pub(crate) fn process_tokens(&mut self, tokens: JsValue) -> Result<String, JsValue> {
+        info!("Starting token processing...");
+
+        let payload_bytes = token_payload_bytes(&tokens);
+        let frame_tokens: Vec<TokenPacket> = match serde_wasm_bindgen::from_value::<Vec<TokenPacket>>(tokens) {
+            Ok(t) => {
+                info!("Successfully deserialized {} tokens", t.len());
+                t
+            },
+            Err(e) => {
+                error!("Failed to deserialize tokens: {:?}", e);
+                return Err(JsValue::from_str(&format!("Token deserialization failed: {:?}", e)));
+            }
+        };
+        self.push_tokens(frame_tokens, payload_bytes)
+    }
+
+    // Decodes token packets into the input queue and keeps them for seeking.
+    // The whole batch is rejected if any token fails to decode.
+    // `payload_bytes` is the batch's size as received, for the bitrate.
+    pub(crate) fn push_tokens(&mut self, frame_tokens: Vec<TokenPacket>, payload_bytes: usize) -> Result<String, JsValue> {
+        let token_count = frame_tokens.len();
+        info!("Processing {} tokens", token_count);
+        self.check_queue_room(token_count).map_err(|e| JsValue::from_str(&e))?;
+        let frames = self.decode_packets(&frame_tokens).map_err(|e| JsValue::from_str(&e))?;
+
+        self.bitrate.record(PayloadKind::Tokens, payload_bytes, token_count);
+        for (frame, token) in frames.into_iter().zip(frame_tokens) {
+            // Room was checked above, so nothing is dropped here
+            self.frame_queue.push(frame);
+            self.token_stream.insert(token.frame_index, token.token, token.is_keyframe);
+        }
+
+        info!("Token processing complete");
+        Ok(format!("Successfully processed {} tokens", token_count))
+    }
+
+    // A batch the queue can't hold is refused rather than partly dropped
+    fn check_queue_room(&self, count: usize) -> Result<(), String> {
+        let free = self.frame_queue.remaining_capacity();
+        if count > free {
+            return Err(format!("Queue full: {} frames sent, room for {}", count, free));
+        }
+        Ok(())
+    }
+
+    fn decode_packets(&self, packets: &[TokenPacket]) -> Result<Vec<Frame>, String> {
+        packets
+            .iter()
+            .map(|token| {
+                debug!("Decoding token for frame {} ({} values)", token.frame_index, token.token.len());
+                let mut frame = decode_token(&token.token, self.width as usize, self.height as usize)
+                    .map_err(|e| format!("Token for frame {}: {}", token.frame_index, e))?;
+                frame.frame_index = token.frame_index;
+                frame.timestamp = token.frame_index as f64 * self.frame_interval;
+                frame.is_keyframe = token.is_keyframe || token.frame_index == 0;
+                Ok(frame)
+            })
+            .collect()
+    }
+
+    pub(crate) fn render_frame(&mut self) -> Result<(), JsValue> {
+        if self.renderer.is_none() {
+            return Ok(());
+        }
+
+        // Adaptive FPS caps the render rate; skipped frames count as dropped
+        if self.metrics.since_last_frame().is_some_and(|since| since < self.min_render_interval) {
+            return Ok(());
+        }
+
+        let render_start = self.metrics.now();
+
+        // Preloaded bitmaps and the debug pattern are always available; token
+        // streams are shown as they come out of the decode queue
+        let drawn = if self.debug_mode || !self.frames.is_empty() {
+            self.present_generated()?
+        } else {
+            self.present_decoded()?
+        };
+        if !drawn {
+            return Ok(());
+        }
+
+        self.frame_count += 1;
+
+        let render_time = self.metrics.now() - render_start;
+        self.metrics.record_processing_time(render_time);
+        self.metrics.mark_frame();
+
+        self.update_adaptive_rate(render_time);
+
+        // Before the HUD, which covers the barcode
+        if self.frame_analyzer.is_some() {
+            self.analyze_presented_frame();
+        }
+
+        if self.quality_log.is_some() {
+            self.track_quality();
+        }
+
+        if self.diagnostic_mode {
+            self.draw_diagnostics()?;
+        }
+        Ok(())
+    }
+
+    // PNG of what is on the render target, or of the last decoded frame when
+    // nothing is attached
+    pub(crate) fn capture_frame(&self) -> Result<Vec<u8>, JsValue> {
+        let frame = match (&self.renderer, &self.last_frame) {
+            (Some(renderer), _) => renderer.capture().map_err(|e| JsValue::from_str(&e))?,
+            (None, Some(frame)) => frame.clone(),
+            (None, None) => return Err(JsValue::from_str("No frame to capture")),
+        };
+        encode_png(&frame).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Quality of the decoded frame at `index` against the loaded ground truth,
+    // None when either is missing
+    pub(crate) fn measure_quality(&mut self, index: usize) -> Result<Option<FrameQuality>, JsValue> {
+        let Some(ground_truth) = self.ground_truth_frame(index)? else {
+            return Ok(None);
+        };
+        let Some(decoded) = self.decode_frame_at(index)? else {
+            return Ok(None);
+        };
+        compare_frames(&decoded, &ground_truth).map(Some).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Measures each frame index the first time it is shown
+    fn track_quality(&mut self) {
+        let index = self.sequencer.current();
+        if self.frames.is_empty() || self.quality_log.as_ref().is_some_and(|log| log.contains(index)) {
+            return;
+        }
+
+        match self.measure_quality(index) {
+            Ok(Some(quality)) => {
+                if let Some(log) = self.quality_log.as_mut() {
+                    log.record(index, quality);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Quality measurement failed for frame {}: {:?}", index, e),
+        }
+    }
+
+    pub(crate) fn start_quality_tracking(&mut self) {
+        self.quality_log = Some(QualityLog::new());
+    }
+
+    // Stops tracking and returns the final report
+    pub(crate) fn stop_quality_tracking(&mut self) -> Result<JsValue, JsValue> {
+        let report = self.quality_report()?;
+        self.quality_log = None;
+        Ok(report)
+    }
+
+    pub(crate) fn quality_report(&self) -> Result<JsValue, JsValue> {
+        let report = self.quality_log.as_ref().map(|log| log.report());
+        Ok(serde_wasm_bindgen::to_value(&report)?)
+    }
+
+    // Flicker and stability of decoded frames `start..=end` against the ground
+    // truth. Frames missing either side break the sequence rather than being
+    // compared across the gap.
+    pub(crate) fn measure_temporal_consistency(&mut self, start: usize, end: usize) -> Result<JsValue, JsValue> {
+        if end < start {
+            return Err(JsValue::from_str("Range end is before its start"));
+        }
+
+        let mut tracker = TemporalTracker::new();
+        for index in start..=end {
+            let pair = match self.ground_truth_frame(index)? {
+                Some(ground_truth) => self.decode_frame_at(index)?.map(|decoded| (decoded, ground_truth)),
+                None => None,
+            };
+            match pair {
+                Some((decoded, ground_truth)) => {
+                    tracker.push(index, &decoded, &ground_truth).map_err(|e| JsValue::from_str(&e))?;
+                }
+                None => tracker.break_sequence(),
+            }
+        }
+        Ok(serde_wasm_bindgen::to_value(&tracker.report())?)
+    }
+
+    // Reads the drawn frame back and feeds its barcode to the analyzer
+    fn analyze_presented_frame(&mut self) {
+        let (Some(renderer), Some(analyzer)) = (self.renderer.as_ref(), self.frame_analyzer.as_mut()) else {
+            return;
+        };
+        match renderer.capture() {
+            Ok(frame) => {
+                analyzer.observe(&frame, self.metrics.now());
+            }
+            Err(e) => warn!("Frame analysis capture failed: {}", e),
+        }
+    }
+
+    // Starts (or restarts) checking presented frames for drops, duplicates
+    // and reordering. Frames need a barcode, e.g. test signals with one.
+    pub(crate) fn start_frame_analysis(&mut self) {
+        self.frame_analyzer = Some(FrameAnalyzer::new());
+    }
+
+    // Stops analysis and returns the final report
+    pub(crate) fn stop_frame_analysis(&mut self) -> Result<JsValue, JsValue> {
+        let report = self.frame_analysis()?;
+        self.frame_analyzer = None;
+        Ok(report)
+    }
+
+    pub(crate) fn frame_analysis(&self) -> Result<JsValue, JsValue> {
+        let report = self.frame_analyzer.as_ref().map(|analyzer| analyzer.report());
+        Ok(serde_wasm_bindgen::to_value(&report)?)
+    }
+
+    // HUD over the frame just drawn
+    fn draw_diagnostics(&mut self) -> Result<(), JsValue> {
+        let (frame_index, pts) = match &self.last_frame {
+            Some(frame) if self.frames.is_empty() && !self.debug_mode => (frame.frame_index, frame.timestamp),
+            _ => {
+                let index = self.sequencer.current();
+                (index, index as f64 * self.frame_interval)
+            }
+        };
+
+        let stats = HudStats {
+            frame_index,
+            pts,
+            packet_bits: self.token_stream.packet_bits(frame_index),
+            frame_budget: self.frame_interval,
+            ..HudStats::collect(&self.metrics, &self.frame_queue.get_metrics(), &self.scheduler.stats())
+        };
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            let (width, height) = renderer.size();
+            let overlay = draw_hud(width as usize, height as usize, &stats);
+            renderer.draw_overlay(&overlay).map_err(|e| JsValue::from_str(&e))?;
+        }
+        Ok(())
+    }
+
+    // Only draws when the scheduler says a new frame is due, or every refresh
+    // while cross-fading
+    fn present_generated(&mut self) -> Result<bool, JsValue> {
+        let steps = match self.scheduler.advance() {
+            Presentation::Present(steps) => steps,
+            Presentation::Hold if self.is_blending() => 0,
+            _ => return Ok(false),
+        };
+
+        if self.debug_mode {
+            let slot = self.scheduler.presented_slot().unwrap_or(0).max(0) as u64;
+            let frame = self.generate_test_frame(slot);
+            self.draw_decoded_frame(&frame)?;
+            return Ok(true);
+        }
+
+        // Skip over frames whose deadline already passed
+        for _ in 0..steps {
+            let step = self.sequencer.step(self.frames.len());
+            self.queue_playback_event(step);
+            if step == Step::Ended {
+                // The last frame is already on screen
+                self.stop_playback();
+                return Ok(false);
+            }
+        }
+
+        if self.compare_view.is_some() {
+            self.present_comparison(self.sequencer.current())?;
+            return Ok(true);
+        }
+
+        self.draw_bitmap(self.sequencer.current())?;
+
+        if self.is_blending() {
+            self.blend_next_bitmap()?;
+        }
+        Ok(true)
+    }
+
+    // Whether frames come from the token stream rather than bitmaps or the
+    // debug pattern
+    fn is_decoded_stream(&self) -> bool {
+        self.frames.is_empty() && !self.debug_mode && !self.token_stream.is_empty()
+    }
+
+    // Shows the newest due frame from the queue output. When nothing new is
+    // ready, or the new frame can't be drawn, the last good frame stays up.
+    fn present_decoded(&mut self) -> Result<bool, JsValue> {
+        if !self.uses_decode_queue() {
+            return self.present_stepped();
+        }
+
+        // Keep a batch decoded ahead so frames are ready before their deadline
+        let (_, _, ready) = self.frame_queue.get_queue_sizes();
+        if ready < self.frame_queue.get_batch_size() {
+            self.frame_queue.process_batch();
+        }
+
+        match self.scheduler.select_frame(self.frame_queue.output_mut()) {
+            Presentation::Present(frame) => {
+                // Position and stepping follow what reached the screen
+                self.sequencer.seek(frame.frame_index, self.frame_total());
+                if let Err(e) = self.draw_decoded_frame(&frame) {
+                    error!("Failed to draw frame at {}ms: {:?}", frame.timestamp, e);
+                    return self.redraw_last_frame();
+                }
+                self.last_frame = Some(frame);
+                Ok(true)
+            }
+            Presentation::Repeat => self.redraw_last_frame(),
+            Presentation::Hold | Presentation::Waiting => Ok(false),
+        }
+    }
+
+    // The decode queue only runs forwards with the media clock
+    fn uses_decode_queue(&self) -> bool {
+        self.scheduler.rate() > 0.0 && self.sequencer.direction() > 0
+    }
+
+    // The decode queue restarts from the frame on screen when it takes over
+    // again
+    fn resume_decode_queue(&mut self, was_queued: bool) -> Result<(), JsValue> {
+        if !was_queued && self.uses_decode_queue() && self.is_decoded_stream() {
+            self.seek_to_frame(self.sequencer.current())?;
+        }
+        Ok(())
+    }
+
+    // Any other playback walks the sequencer through the token stream and
+    // decodes each frame it lands on, like bitmap playback does. A ping-pong
+    // bounce that turns it forwards hands back to the decode queue.
+    fn present_stepped(&mut self) -> Result<bool, JsValue> {
+        let steps = match self.scheduler.advance() {
+            Presentation::Present(steps) => steps,
+            _ => return Ok(false),
+        };
+
+        let frame_count = self.frame_total();
+        for _ in 0..steps {
+            let step = self.sequencer.step(frame_count);
+            self.queue_playback_event(step);
+            if step == Step::Ended {
+                self.stop_playback();
+                return Ok(false);
+            }
+        }
+        if self.uses_decode_queue() {
+            self.seek_to_frame(self.sequencer.current())?;
+            return Ok(true);
+        }
+
+        // Gaps and evicted tokens keep the last frame up
+        match self.decode_frame_at(self.sequencer.current())? {
+            Some(frame) => {
+                self.draw_decoded_frame(&frame)?;
+                self.last_frame = Some(frame);
+                Ok(true)
+            }
+            None => self.redraw_last_frame(),
+        }
+    }
+
+    fn redraw_last_frame(&mut self) -> Result<bool, JsValue> {
+        let (Some(renderer), Some(frame)) = (self.renderer.as_mut(), &self.last_frame) else {
+            return Ok(false);
+        };
+        renderer.present(frame).map_err(|e| JsValue::from_str(&e))?;
+        Ok(true)
+    }
+
+    // Total frames available to seek in: loaded bitmaps, or the token stream
+    pub(crate) fn frame_total(&self) -> usize {
+        if !self.frames.is_empty() {
+            self.frames.len()
+        } else {
+            self.token_stream.frame_count()
+        }
+    }
+
+    pub(crate) fn current_frame(&self) -> usize {
+        self.sequencer.current()
+    }
+
+    pub(crate) fn seek_to_frame(&mut self, frame_index: usize) -> Result<(), JsValue> {
+        let frame_count = self.frame_total();
+        if frame_count == 0 {
+            return Err(JsValue::from_str("No frames loaded to seek in"));
+        }
+
+        // Tokens older than the retention window are gone
+        let first = if self.frames.is_empty() { self.token_stream.first_frame().unwrap_or(0) } else { 0 };
+        let index = frame_index.clamp(first, frame_count - 1);
+        self.sequencer.seek(index, frame_count);
+
+        if self.compare_view.is_some() && !self.frames.is_empty() {
+            self.present_comparison(index)?;
+        } else if !self.frames.is_empty() {
+            self.draw_bitmap(index)?;
+        } else {
+            // Show the target and queue as many of the rest as the queue holds
+            let last = (frame_count - 1).min(index + self.frame_queue.get_max_size());
+            let decoded = self.token_stream
+                .decode_range(
+                    index,
+                    last,
+                    self.width as usize,
+                    self.height as usize,
+                    self.frame_interval,
+                )
+                .map_err(|e| JsValue::from_str(&e))?;
+
+            self.frame_queue.flush();
+            let mut decoded = decoded.into_iter();
+            if let Some(target) = decoded.next() {
+                let target = self.frame_queue
+                    .filter_frame(target)
+                    .map_err(|e| JsValue::from_str(&e))?;
+                self.draw_decoded_frame(&target)?;
+                self.last_frame = Some(target);
+            }
+            for frame in decoded {
+                let queued = self.frame_queue.push(frame);
+                debug_assert!(queued, "seek queued more frames than the queue holds");
+            }
+        }
+
+        self.scheduler.seek(index as f64 * self.frame_interval);
+        debug!("Seeked to frame {}", index);
+        Ok(())
+    }
+
+    pub(crate) fn seek_to_time(&mut self, time_ms: f64) -> Result<(), JsValue> {
+        let index = (time_ms.max(0.0) / self.frame_interval).floor() as usize;
+        self.seek_to_frame(index)
+    }
+
+    pub(crate) fn position(&self) -> JsValue {
+        let position = js_sys::Object::new();
+        let frame = self.sequencer.current();
+
+        js_sys::Reflect::set(
+            &position,
+            &"frame".into(),
+            &(frame as u32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &position,
+            &"time".into(),
+            &(frame as f64 * self.frame_interval).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &position,
+            &"frameCount".into(),
+            &(self.frame_total() as u32).into()
+        ).unwrap();
+
+        js_sys::Reflect::set(
+            &position,
+            &"playing".into(),
+            &self.is_playing.into()
+        ).unwrap();
+
+        position.into()
+    }
+
+    fn draw_bitmap(&mut self, index: usize) -> Result<(), JsValue> {
+        self.present_bitmap(index, 1.0)
+    }
+
+    // Fades towards the next frame by how far the media clock is into this one
+    fn blend_next_bitmap(&mut self) -> Result<(), JsValue> {
+        let phase = if self.scheduler.rate() < 0.0 {
+            1.0 - self.scheduler.phase()
+        } else {
+            self.scheduler.phase()
+        };
+
+        match self.sequencer.peek(self.frames.len()) {
+            Some(next) if phase > 0.0 => self.present_bitmap(next, phase),
+            _ => Ok(()),
+        }
+    }
+
+    fn present_bitmap(&mut self, index: usize, alpha: f64) -> Result<(), JsValue> {
+        if let (Some(renderer), Some(bitmap)) = (self.renderer.as_mut(), self.frames.get(index)) {
+            renderer.present_bitmap(bitmap, alpha).map_err(|e| JsValue::from_str(&e))?;
+        }
+        Ok(())
+    }
+
+    // Composes the ground truth at `index`, the ground truth at its keyframe
+    // and the decoded frame, so all three stay in lockstep
+    fn present_comparison(&mut self, index: usize) -> Result<(), JsValue> {
+        let Some(view) = self.compare_view else {
+            return Ok(());
+        };
+
+        let ground_truth = self.ground_truth_frame(index)?;
+        let reference = self.ground_truth_frame(self.token_stream.keyframe_before(index).unwrap_or(0))?;
+        let decoded = self.decode_frame_at(index)?;
+
+        let composite = view.compose(&ComparePanels {
+            ground_truth: ground_truth.as_ref(),
+            reference: reference.as_ref(),
+            decoded: decoded.as_ref(),
+        }).map_err(|e| JsValue::from_str(&e))?;
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            if renderer.size() != (composite.width as u32, composite.height as u32) {
+                renderer
+                    .resize(composite.width as u32, composite.height as u32)
+                    .map_err(|e| JsValue::from_str(&e))?;
+            }
+            renderer.present(&composite).map_err(|e| JsValue::from_str(&e))?;
+        }
+        Ok(())
+    }
+
+    // Pixels of the loaded ground-truth bitmap at `index`
+    fn ground_truth_frame(&mut self, index: usize) -> Result<Option<Frame>, JsValue> {
+        let Some(bitmap) = self.frames.get(index) else {
+            return Ok(None);
+        };
+        if self.bitmap_reader.is_none() {
+            self.bitmap_reader = Some(BitmapReader::new().map_err(|e| JsValue::from_str(&e))?);
+        }
+        let reader = self.bitmap_reader.as_mut().unwrap();
+        reader.read(bitmap).map(Some).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Decodes the token for `index` through a separate copy of the CPU filter
+    // chain, so stateful filters like temporal_smooth keep the playback
+    // history. None when the token is missing or doesn't decode.
+    fn decode_frame_at(&self, index: usize) -> Result<Option<Frame>, JsValue> {
+        let decoded = self.token_stream.decode_frame(
+            index,
+            self.width as usize,
+            self.height as usize,
+            self.frame_interval,
+        );
+        match decoded {
+            Some(Ok(frame)) => FilterChain::from_configs(&self.cpu_filters)
+                .and_then(|mut filters| filters.apply(frame))
+                .map(Some)
+                .map_err(|e| JsValue::from_str(&e)),
+            Some(Err(e)) => {
+                warn!("Frame {} can't be decoded: {}", index, e);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn draw_decoded_frame(&mut self, frame: &Frame) -> Result<(), JsValue> {
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.present(frame).map_err(|e| JsValue::from_str(&e))?;
+        }
+        Ok(())
+    }
+
+    // Events are delivered after the render so callbacks can safely call
+    // back into the decoder
+    fn queue_playback_event(&mut self, step: Step) {
+        let (Some(_), Some(name)) = (&self.playback_callback, step.event_name()) else {
+            return;
+        };
+
+        let event = js_sys::Object::new();
+        js_sys::Reflect::set(&event, &"type".into(), &name.into()).unwrap();
+        js_sys::Reflect::set(&event, &"frame".into(), &(self.sequencer.current() as u32).into()).unwrap();
+        js_sys::Reflect::set(&event, &"mode".into(), &format!("{:?}", self.sequencer.mode()).into()).unwrap();
+        self.pending_events.push(event.into());
+    }
+
+    pub(crate) fn take_playback_events(&mut self) -> Option<(js_sys::Function, Vec<JsValue>)> {
+        if self.pending_events.is_empty() {
+            return None;
+        }
+        let events = std::mem::take(&mut self.pending_events);
+        self.playback_callback.clone().map(|callback| (callback, events))
+    }
+
+    pub(crate) fn set_playback_mode(&mut self, mode: PlaybackMode) {
+        let was_queued = self.uses_decode_queue();
+        self.sequencer.set_mode(mode);
+        if let Err(e) = self.resume_decode_queue(was_queued) {
+            warn!("Failed to restart decoding: {:?}", e);
+        }
+        info!("Playback mode set to: {:?}", mode);
+    }
+
+    pub(crate) fn playback_mode(&self) -> PlaybackMode {
+        self.sequencer.mode()
+    }
+
+    pub(crate) fn set_playback_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.playback_callback = callback;
+    }
+
+    pub(crate) fn set_playback_direction(&mut self, direction: i32) {
+        let was_queued = self.uses_decode_queue();
+        self.sequencer.set_direction(direction);
+        if let Err(e) = self.resume_decode_queue(was_queued) {
+            warn!("Failed to restart decoding: {:?}", e);
+        }
+    }
+
+    pub(crate) fn playback_direction(&self) -> i32 {
+        self.sequencer.direction()
+    }
+
+    pub(crate) fn process_batch(&mut self) -> Result<String, JsValue> {
+        info!("Processing batch...");
+        let processed = self.frame_queue.process_batch();
+        Ok(format!("Processed batch: {} frames", processed.len()))
+    }
+
+    // Decodes everything queued and hands back the frames with their
+    // indices, for callers that pass frames on instead of presenting them
+    pub(crate) fn decode_pending(&mut self) -> Vec<(usize, Frame)> {
+        self.frame_queue
+            .drain_processed()
+            .into_iter()
+            .map(|frame| (frame.frame_index, frame))
+            .collect()
+    }
+
+    pub(crate) fn queue_stats(&self) -> QueueStats {
+        self.frame_queue.get_metrics()
+    }
+
+    pub(crate) fn reference_status(&self) -> String {
+        match &self.reference_data {
+            Some(ref_data) => format!(
+                "Reference data loaded: {} features",
+                ref_data.features.len()
+            ),
+            None => "No reference data loaded".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::VirtualClock;
+    use std::rc::Rc;
+
+    // 2x2 decoder at 25 fps with `count` tokens pushed, a keyframe every 4
+    fn decoder_with_tokens(clock: &VirtualClock, count: usize) -> DecoderState {
+        let mut state = DecoderState::new(2, 2, Rc::new(clock.clone()));
+        state.set_target_fps(25);
+        state.attach_headless(2, 2);
+        let tokens = (0..count)
+            .map(|i| TokenPacket { frame_index: i, token: vec![i as f32; 16], is_keyframe: i % 4 == 0 })
+            .collect();
+        // As plain JS arrays, eight bytes per value
+        state.push_tokens(tokens, count * 16 * 8).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_decoded_batches_keep_token_indices() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 6);
+        // Timestamps were set at 25 fps; the index mustn't be derived from them
+        state.set_target_fps(50);
+
+        // The whole buffer, more than one batch, and nothing kept afterwards
+        let indices: Vec<_> = state.decode_pending().into_iter().map(|(index, _)| index).collect();
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(state.frame_queue.get_queue_sizes(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_batches_decode_whole_or_not_at_all() {
+        let clock = VirtualClock::new(0.0);
+        let state = decoder_with_tokens(&clock, 2);
+        // Counted as received, not from the decoded f32 values
+        assert_eq!((state.bitrate.stats().token_bytes, state.bitrate.stats().packets), (256, 1));
+
+        let batch = vec![
+            TokenPacket { frame_index: 2, token: vec![2.0; 16], is_keyframe: false },
+            TokenPacket { frame_index: 3, token: vec![3.0; 5], is_keyframe: false },
+        ];
+        let error = state.decode_packets(&batch).err().unwrap();
+        assert!(error.starts_with("Token for frame 3"), "{}", error);
+        assert_eq!(state.decode_packets(&batch[..1]).unwrap()[0].frame_index, 2);
+    }
+
+    #[test]
+    fn test_full_queue_refuses_instead_of_dropping() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 10);
+        state.frame_queue = FrameQueue::with_clock(3, 2, Rc::new(clock.clone()));
+
+        assert!(state.check_queue_room(3).is_ok());
+        assert_eq!(state.check_queue_room(4).err().unwrap(), "Queue full: 4 frames sent, room for 3");
+
+        // A seek only decodes what fits behind the target
+        state.seek_to_frame(2).unwrap();
+        let stats = state.queue_stats();
+        assert_eq!((stats.input_queue_size, stats.frames_dropped), (3, 0));
+    }
+
+    #[test]
+    fn test_decoded_playback_moves_position() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 10);
+
+        state.start_playback();
+        for _ in 0..5 {
+            state.render_frame().unwrap();
+            clock.advance(40.0);
+        }
+        state.stop_playback();
+        assert_eq!(state.current_frame(), 4);
+
+        // Stepping continues from the frame on screen
+        let current = state.current_frame();
+        state.seek_to_frame(current + 1).unwrap();
+        assert_eq!(state.current_frame(), 5);
+        assert_eq!(state.last_frame.as_ref().map(|frame| frame.data[0]), Some(5));
+    }
+
+    #[test]
+    fn test_side_decodes_leave_playback_filters_alone() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 10);
+        state.cpu_filters = vec![FilterConfig::TemporalSmooth { strength: 0.5, scene_cut: 255.0 }];
+        state.frame_queue.set_filters(FilterChain::from_configs(&state.cpu_filters).unwrap());
+
+        // Each side decode starts from an empty history
+        let far = state.decode_frame_at(9).unwrap().unwrap();
+        let near = state.decode_frame_at(2).unwrap().unwrap();
+        assert_eq!((far.data[0], near.data[0]), (9, 2));
+
+        // Playback's chain hasn't seen a frame, so nothing is blended in
+        assert_eq!(state.frame_queue.filter_frame(Frame::new(2, 2)).unwrap().data[0], 0);
+    }
+
+    #[test]
+    fn test_scheduler_drops_show_as_barcode_gaps() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = DecoderState::new(160, 90, Rc::new(clock.clone()));
+        state.set_target_fps(25);
+        state.attach_headless(160, 90);
+        state.debug_mode = true;
+        state.set_test_signal(TestSignal::MovingBox, true);
+        state.start_frame_analysis();
+
+        state.start_playback();
+        for wait in [40.0, 40.0, 80.0, 40.0, 0.0] {
+            state.render_frame().unwrap();
+            clock.advance(wait);
+        }
+
+        let report = state.frame_analyzer.as_ref().unwrap().report();
+        assert_eq!((report.first_index, report.last_index), (Some(0), Some(5)));
+        assert_eq!((report.unique_frames, report.dropped), (5, 1));
+        assert_eq!(state.scheduler.stats().dropped, 1);
+    }
+
+    // Stands in for a canvas backend: only what was drawn can be read back
+    struct CanvasStub {
+        shown: Frame,
+    }
+
+    impl Renderer for CanvasStub {
+        fn kind(&self) -> RendererKind {
+            RendererKind::Canvas2d
+        }
+
+        fn size(&self) -> (u32, u32) {
+            (self.shown.width as u32, self.shown.height as u32)
+        }
+
+        fn present(&mut self, frame: &Frame) -> Result<(), String> {
+            self.shown = frame.to_rgba()?;
+            Ok(())
+        }
+
+        fn present_bitmap(&mut self, _bitmap: &ImageBitmap, _alpha: f64) -> Result<(), String> {
+            Err("No bitmaps".to_string())
+        }
+
+        fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+            self.shown = Frame::new(width as usize, height as usize);
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), String> {
+            self.shown.data.fill(0);
+            Ok(())
+        }
+
+        fn draw_overlay(&mut self, _overlay: &Frame) -> Result<(), String> {
+            self.shown.data.fill(255);
+            Ok(())
+        }
+
+        fn capture(&self) -> Result<Frame, String> {
+            Ok(self.shown.clone())
+        }
+    }
+
+    #[test]
+    fn test_capture_reads_the_render_target() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 4);
+        state.set_renderer(Box::new(CanvasStub { shown: Frame::new(2, 2) }));
+
+        state.start_playback();
+        state.render_frame().unwrap();
+        let shown = state.last_frame.clone().unwrap();
+        assert_eq!(state.capture_frame().unwrap(), encode_png(&shown).unwrap());
+
+        // Overlays drawn over the frame are part of the capture
+        state.diagnostic_mode = true;
+        clock.advance(40.0);
+        state.render_frame().unwrap();
+        assert_eq!(state.capture_frame().unwrap(), encode_png(&Frame { data: vec![255; 16], ..shown }).unwrap());
+    }
+
+    #[test]
+    fn test_decoded_stream_plays_in_reverse() {
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 10);
+        let shown = |state: &DecoderState| state.last_frame.as_ref().map(|frame| frame.data[0] as usize);
+
+        state.start_playback();
+        for _ in 0..5 {
+            state.render_frame().unwrap();
+            clock.advance(40.0);
+        }
+        assert_eq!(shown(&state), Some(4));
+
+        state.set_playback_rate(-1.0).unwrap();
+        let mut reversed = Vec::new();
+        for _ in 0..4 {
+            state.render_frame().unwrap();
+            reversed.push((state.current_frame(), shown(&state)));
+            clock.advance(40.0);
+        }
+        assert_eq!(reversed, vec![(3, Some(3)), (2, Some(2)), (1, Some(1)), (0, Some(0))]);
+
+        // Forward again carries on from the frame on screen
+        state.set_playback_rate(1.0).unwrap();
+        clock.advance(40.0);
+        state.render_frame().unwrap();
+        assert_eq!((state.current_frame(), shown(&state)), (1, Some(1)));
+    }
+
+    // Frames shown over `count` renders, 40ms apart
+    fn play(state: &mut DecoderState, clock: &VirtualClock, count: usize) -> Vec<usize> {
+        (0..count)
+            .map(|_| {
+                clock.advance(40.0);
+                state.render_frame().unwrap();
+                state.last_frame.as_ref().unwrap().data[0] as usize
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_negative_rate_in_reverse_modes() {
+        // Ping-pong at a negative rate bounces off the first frame and keeps
+        // going
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 6);
+        state.seek_to_frame(1).unwrap();
+        state.set_playback_rate(-1.0).unwrap();
+        state.start_playback();
+        assert_eq!(play(&mut state, &clock, 4), [0, 1, 2, 3]);
+        assert_eq!(state.playback_direction(), 1);
+
+        // Bouncing forwards at a positive rate hands back to the decode queue
+        state.set_playback_rate(1.0).unwrap();
+        state.set_playback_direction(-1);
+        assert_eq!(play(&mut state, &clock, 5), [2, 1, 0, 1, 2]);
+
+        // ReverseLoop at a negative rate plays forwards
+        let clock = VirtualClock::new(0.0);
+        let mut state = decoder_with_tokens(&clock, 6);
+        state.set_playback_mode(PlaybackMode::ReverseLoop);
+        state.seek_to_frame(2).unwrap();
+        state.set_playback_rate(-1.0).unwrap();
+        state.start_playback();
+        assert_eq!(play(&mut state, &clock, 3), [3, 4, 5]);
+
+        // and backwards again at a positive rate
+        state.set_playback_rate(1.0).unwrap();
+        assert_eq!(play(&mut state, &clock, 3), [4, 3, 2]);
+    }
+}
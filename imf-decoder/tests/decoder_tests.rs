@@ -0,0 +1,111 @@
+// Runs with `wasm-pack test --headless --firefox`
+#![cfg(target_arch = "wasm32")]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+use imf_decoder::decoder::DecoderWorker;
+use imf_decoder::player::{Player, PlayerStatus};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// Stands in for the worker boundary the way js/decoder/worker.ts wires it:
+// the Player's Worker and the DecoderWorker's global postMessage are the two
+// ends of a MessageChannel, so every message is cloned and transferred
+#[wasm_bindgen(inline_js = r#"
+export function install_loopback(handle) {
+    const channel = new MessageChannel();
+    globalThis.postMessage = (data, transfer) => channel.port2.postMessage(data, transfer || []);
+    channel.port2.onmessage = (event) => handle(event.data);
+    globalThis.Worker = class {
+        constructor() {
+            channel.port1.onmessage = (event) => this.onmessage && this.onmessage(event);
+        }
+        postMessage(data, transfer) {
+            channel.port1.postMessage(data, transfer || []);
+        }
+        terminate() {
+            channel.port1.close();
+            channel.port2.close();
+        }
+    };
+}
+"#)]
+extern "C" {
+    fn install_loopback(handle: &js_sys::Function);
+}
+
+// Resolves with the next payload `set` hands its callback
+fn next_callback(player: &mut Player, set: fn(&mut Player, Option<js_sys::Function>)) -> JsFuture {
+    let mut resolve = None;
+    let promise = js_sys::Promise::new(&mut |done, _| resolve = Some(done));
+    set(player, resolve);
+    JsFuture::from(promise)
+}
+
+// `[{ frame_index, token }]` the way a JS caller passes tokens to decode
+fn packets(frame_index: usize, token: &js_sys::Float32Array) -> JsValue {
+    let packet = js_sys::Object::new();
+    js_sys::Reflect::set(&packet, &"frame_index".into(), &(frame_index as u32).into()).unwrap();
+    js_sys::Reflect::set(&packet, &"token".into(), token).unwrap();
+    js_sys::Array::of1(&packet).into()
+}
+
+async fn status_after(player: &mut Player, send: impl FnOnce(&mut Player) -> Result<(), JsValue>) -> u32 {
+    let status = next_callback(player, Player::set_status_callback);
+    send(player).unwrap();
+    status.await.unwrap().as_f64().unwrap() as u32
+}
+
+#[wasm_bindgen_test]
+async fn test_player_round_trip() {
+    let worker: Rc<RefCell<Option<DecoderWorker>>> = Rc::new(RefCell::new(None));
+    let handler_worker = worker.clone();
+    let handle = Closure::wrap(Box::new(move |data: JsValue| {
+        if let Some(worker) = handler_worker.borrow_mut().as_mut() {
+            worker.handle_message(data);
+        }
+    }) as Box<dyn FnMut(JsValue)>);
+    install_loopback(handle.as_ref().unchecked_ref());
+    *worker.borrow_mut() = Some(DecoderWorker::new().unwrap());
+
+    // 2x2 RGBA, so a token is 16 values
+    let mut player = Player::new(2, 2).unwrap();
+    assert_eq!(player.get_status(), PlayerStatus::Idle);
+    assert_eq!(status_after(&mut player, Player::initialize).await, PlayerStatus::Ready as u32);
+    assert_eq!(status_after(&mut player, Player::start).await, PlayerStatus::Playing as u32);
+
+    // One token in, one frame out with the token's index and values as pixels
+    let values: Vec<f32> = (0..16).map(|i| (i * 10) as f32).collect();
+    let token = js_sys::Float32Array::from(&values[..]);
+    let frame = next_callback(&mut player, Player::set_frame_callback);
+    player.decode(packets(7, &token)).unwrap();
+    // The token's buffer was transferred rather than copied
+    assert_eq!(token.byte_length(), 0);
+
+    let frame = frame.await.unwrap();
+    let index = js_sys::Reflect::get(&frame, &"frameIndex".into()).unwrap();
+    assert_eq!(index.as_f64(), Some(7.0));
+    let pixels = js_sys::Reflect::get(&frame, &"data".into()).unwrap();
+    let expected: Vec<u8> = values.iter().map(|&x| x as u8).collect();
+    assert_eq!(pixels.dyn_into::<js_sys::Uint8Array>().unwrap().to_vec(), expected);
+
+    // A token that doesn't fit the frame size comes back as a decode error
+    let error = next_callback(&mut player, Player::set_error_callback);
+    player.decode(packets(8, &js_sys::Float32Array::new_with_length(3))).unwrap();
+    let error = error.await.unwrap();
+    let kind = js_sys::Reflect::get(&error, &"kind".into()).unwrap();
+    assert_eq!(kind.as_string().as_deref(), Some("Decode"));
+    let message = js_sys::Reflect::get(&error, &"message".into()).unwrap().as_string().unwrap();
+    assert!(message.contains("Token length 3"), "{}", message);
+
+    // A closed decoder has to be reset before it can be opened again
+    assert_eq!(status_after(&mut player, Player::close).await, PlayerStatus::Closed as u32);
+    assert!(player.initialize().is_err());
+    assert_eq!(status_after(&mut player, Player::reset).await, PlayerStatus::Idle as u32);
+    assert_eq!(status_after(&mut player, Player::initialize).await, PlayerStatus::Ready as u32);
+
+    player.destroy();
+}